@@ -0,0 +1,159 @@
+use anyhow::{anyhow, bail};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+/// The key that marks a JSON object as an encoded array, if the eval and tool agreed on the
+/// `"arrays"` feature.
+const KEY: &str = "$array";
+
+/// The type of the elements of an encoded array, each of which is stored little-endian.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Dtype {
+    F64,
+    F32,
+    I64,
+    I32,
+}
+
+impl Dtype {
+    /// Get the number of bytes in each element.
+    fn size(self) -> usize {
+        match self {
+            Dtype::F64 | Dtype::I64 => 8,
+            Dtype::F32 | Dtype::I32 => 4,
+        }
+    }
+
+    /// Get the JSON number stored in the `bytes` of one element.
+    fn number(self, bytes: &[u8]) -> anyhow::Result<Number> {
+        let float = |x: f64| {
+            Number::from_f64(x).ok_or_else(|| anyhow!("encoded array contains {x}, unlike JSON"))
+        };
+        match self {
+            Dtype::F64 => float(f64::from_le_bytes(bytes.try_into()?)),
+            Dtype::F32 => float(f32::from_le_bytes(bytes.try_into()?).into()),
+            Dtype::I64 => Ok(i64::from_le_bytes(bytes.try_into()?).into()),
+            Dtype::I32 => Ok(i32::from_le_bytes(bytes.try_into()?).into()),
+        }
+    }
+}
+
+/// A dense array of numbers, encoded in place of nested JSON arrays.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Encoded {
+    /// The type of the elements.
+    #[serde(rename = "$array")]
+    dtype: Dtype,
+
+    /// The length of each dimension, outermost first.
+    shape: Vec<usize>,
+
+    /// The elements in row-major order.
+    base64: String,
+}
+
+impl Encoded {
+    /// Decode into nested JSON arrays of numbers.
+    fn decode(self) -> anyhow::Result<Value> {
+        let Encoded {
+            dtype,
+            shape,
+            base64,
+        } = self;
+        let bytes = STANDARD.decode(base64)?;
+        let count = shape
+            .iter()
+            .try_fold(1usize, |count, &n| count.checked_mul(n))
+            .ok_or_else(|| anyhow!("encoded array shape {shape:?} is too large"))?;
+        if Some(bytes.len()) != count.checked_mul(dtype.size()) {
+            bail!(
+                "encoded array of shape {shape:?} has {} bytes, which don't fit {count} elements \
+                 of type {dtype:?}",
+                bytes.len(),
+            );
+        }
+        let numbers = bytes
+            .chunks_exact(dtype.size())
+            .map(|chunk| Ok(Value::Number(dtype.number(chunk)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(nest(&mut numbers.into_iter(), &shape))
+    }
+}
+
+/// Arrange `numbers` into nested arrays of the given `shape`.
+fn nest(numbers: &mut impl Iterator<Item = Value>, shape: &[usize]) -> Value {
+    match shape.split_first() {
+        None => numbers.next().unwrap_or_default(),
+        Some((&n, rest)) => Value::Array((0..n).map(|_| nest(numbers, rest)).collect()),
+    }
+}
+
+/// Decode one encoded array from the fields of a JSON object.
+fn decode(map: Map<String, Value>) -> anyhow::Result<Value> {
+    Encoded::deserialize(Value::Object(map))?.decode()
+}
+
+/// Replace every encoded array in `value` with nested JSON arrays of numbers, so that it can be
+/// compared with a value that wasn't encoded.
+pub fn decode_arrays(value: &mut Value) -> anyhow::Result<()> {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                decode_arrays(item)?;
+            }
+        }
+        Value::Object(map) if map.contains_key(KEY) => *value = decode(std::mem::take(map))?,
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                decode_arrays(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::decode_arrays;
+
+    #[test]
+    fn test_decode_arrays_f64() {
+        let bytes: Vec<u8> = [1.5f64, -2., 0.25, 4., 5., 6.]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect();
+        let mut value = json!({
+            "x": { "$array": "f64", "shape": [2, 3], "base64": STANDARD.encode(bytes) },
+            "n": 3,
+        });
+        decode_arrays(&mut value).unwrap();
+        assert_eq!(
+            value,
+            json!({ "x": [[1.5, -2.0, 0.25], [4.0, 5.0, 6.0]], "n": 3 }),
+        );
+    }
+
+    #[test]
+    fn test_decode_arrays_i32() {
+        let bytes: Vec<u8> = [7i32, -1].iter().flat_map(|x| x.to_le_bytes()).collect();
+        let encoded = json!({ "$array": "i32", "shape": [2], "base64": STANDARD.encode(bytes) });
+        let mut value = json!([encoded, []]);
+        decode_arrays(&mut value).unwrap();
+        assert_eq!(value, json!([[7, -1], []]));
+    }
+
+    #[test]
+    fn test_decode_arrays_wrong_size() {
+        let bytes = 1.0f64.to_le_bytes();
+        let mut value = json!({ "$array": "f64", "shape": [2], "base64": STANDARD.encode(bytes) });
+        assert!(decode_arrays(&mut value).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use serde::Serialize;
+
+use crate::protocol::{Crash, Id, Message, Nanoseconds, Phase, Timing};
+
+/// The version of the event format.
+///
+/// New fields and kinds of events may be added without changing the version, but it is
+/// incremented whenever an existing field is removed or changes meaning.
+pub const VERSION: u32 = 1;
+
+/// An event in a session, which is written as one line of JSON.
+#[derive(Debug, Serialize)]
+pub struct Event {
+    /// The version of the event format.
+    pub version: u32,
+
+    /// The time since the session started.
+    pub elapsed: Nanoseconds,
+
+    /// What happened.
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl Event {
+    /// Make an event that happened at `elapsed`.
+    pub fn new(elapsed: Duration, kind: EventKind) -> Self {
+        Self {
+            version: VERSION,
+            elapsed: Nanoseconds {
+                nanoseconds: elapsed.as_nanos(),
+            },
+            kind,
+        }
+    }
+
+    /// Write the event as a line of JSON, flushing so that it can be followed live.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        serde_json::to_writer(&mut out, self)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
+
+/// The kinds of events.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// A message was sent to the tool, which it hasn't responded to yet.
+    Message(Sent),
+
+    /// The tool responded to a message from the eval.
+    Round(Round),
+
+    /// A tool did not respond to a message in time.
+    Timeout {
+        /// The message ID.
+        id: Id,
+
+        /// The position of the tool among the other tools, if it is not the first tool.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        other: Option<usize>,
+
+        /// The phase whose time limit was exceeded.
+        phase: Phase,
+
+        /// The time limit in nanoseconds.
+        nanoseconds: u128,
+    },
+
+    /// The tool did not respond to a message in time, but cancelled it so the session could go on.
+    Cancel {
+        /// The message ID.
+        id: Id,
+
+        /// The phase whose time limit was exceeded.
+        phase: Phase,
+
+        /// The time limit in nanoseconds.
+        nanoseconds: u128,
+    },
+
+    /// The output of another tool differed from that of the first tool.
+    Mismatch {
+        /// The message ID.
+        id: Id,
+
+        /// The position of the tool among the other tools.
+        other: usize,
+
+        /// How the output differed.
+        error: String,
+    },
+
+    /// A tool exited in the middle of the session.
+    Crash {
+        /// The position of the tool among the other tools, if it is not the first tool.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        other: Option<usize>,
+
+        /// How the tool exited.
+        #[serde(flatten)]
+        crash: Crash,
+    },
+
+    /// The session was cut short by an error, such as invalid JSON or a protocol violation.
+    Error {
+        /// A description of the error.
+        error: String,
+    },
+
+    /// The session is over.
+    End {
+        /// The outcome of the session, as given to `gradbench exit-code`.
+        outcome: &'static str,
+    },
+}
+
+/// A message that was sent to the tool.
+#[derive(Debug, Default, Serialize)]
+pub struct Sent {
+    /// The message ID.
+    pub id: Id,
+
+    /// The kind of message.
+    pub kind: &'static str,
+
+    /// The module of a `"define"` or `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    /// The function of an `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+
+    /// The description of the input of an `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The ID of the `"evaluate"` message that an `"analysis"` message is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub of: Option<Id>,
+}
+
+impl Sent {
+    /// Describe a `message` that was sent to the tool.
+    pub fn new(message: &Message) -> Self {
+        let sent = Self {
+            id: message.id(),
+            kind: message.kind(),
+            ..Default::default()
+        };
+        match message {
+            Message::Define { module, .. } => Self {
+                module: Some(module.clone()),
+                ..sent
+            },
+            Message::Evaluate {
+                module,
+                function,
+                description,
+                ..
+            } => Self {
+                module: Some(module.clone()),
+                function: Some(function.clone()),
+                description: description.clone(),
+                ..sent
+            },
+            Message::Analysis { of, .. } => Self {
+                of: Some(*of),
+                ..sent
+            },
+            Message::Start { .. } | Message::End { .. } | Message::Cancel { .. } => sent,
+        }
+    }
+}
+
+/// A message from the eval and the response from the tool.
+#[derive(Debug, Default, Serialize)]
+pub struct Round {
+    /// The message ID.
+    pub id: Id,
+
+    /// The kind of message.
+    pub kind: &'static str,
+
+    /// The module of a `"define"` or `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+
+    /// The function of an `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+
+    /// The description of the input of an `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The ID of the `"evaluate"` message that an `"analysis"` message is about.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub of: Option<Id>,
+
+    /// How many nanoseconds the tool took to respond.
+    pub nanoseconds: u128,
+
+    /// The tool name from the response to a `"start"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+
+    /// Whether the tool succeeded, for a `"define"` or `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+
+    /// Whether the output was valid, for an `"analysis"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid: Option<bool>,
+
+    /// The timings from the tool, for a `"define"` or `"evaluate"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Vec<Timing>>,
+
+    /// The error from the tool, or from the eval for an `"analysis"` message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Round {
+    /// Start describing the round for a `message`, to which the tool took `nanoseconds` to respond.
+    pub fn new(message: &Message, nanoseconds: u128) -> Self {
+        let round = Self {
+            id: message.id(),
+            kind: message.kind(),
+            nanoseconds,
+            ..Default::default()
+        };
+        match message {
+            Message::Start { .. } | Message::End { .. } | Message::Cancel { .. } => round,
+            Message::Define { module, .. } => Self {
+                module: Some(module.clone()),
+                ..round
+            },
+            Message::Evaluate {
+                module,
+                function,
+                description,
+                ..
+            } => Self {
+                module: Some(module.clone()),
+                function: Some(function.clone()),
+                description: description.clone(),
+                ..round
+            },
+            Message::Analysis {
+                of, valid, error, ..
+            } => Self {
+                of: Some(*of),
+                valid: Some(*valid),
+                error: error.clone(),
+                ..round
+            },
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::{collections::HashMap, io::BufRead, str::FromStr};
+
+use anyhow::{anyhow, bail};
+use serde_json::Value;
+
+use crate::{
+    arrays::decode_arrays,
+    protocol::{EvaluateResponse, Id, LogMessage, LogResponse, Message},
+    util::{compare_json_by, try_read_line, Tolerance},
+};
+
+/// Identifies an evaluation across sessions, as the nth evaluation of a function with a given
+/// description, so that it doesn't depend on message IDs.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Key {
+    module: String,
+    function: String,
+    description: Option<String>,
+    occurrence: usize,
+}
+
+/// Counts the evaluations seen so far in a session, to give each one a [`Key`].
+#[derive(Debug, Default)]
+pub struct Occurrences {
+    counts: HashMap<(String, String, Option<String>), usize>,
+}
+
+impl Occurrences {
+    /// Get the key of an evaluate `message`, if it is one, counting it as seen.
+    pub fn key(&mut self, message: &Message) -> Option<Key> {
+        let Message::Evaluate {
+            module,
+            function,
+            description,
+            ..
+        } = message
+        else {
+            return None;
+        };
+        let count = self
+            .counts
+            .entry((module.clone(), function.clone(), description.clone()))
+            .or_default();
+        let occurrence = *count;
+        *count += 1;
+        Some(Key {
+            module: module.clone(),
+            function: function.clone(),
+            description: description.clone(),
+            occurrence,
+        })
+    }
+}
+
+/// The tolerance for the numbers in one field of an output, and everything nested in it.
+#[derive(Clone, Debug)]
+pub struct FieldTolerance {
+    /// The path of the field, as printed when outputs differ, e.g. `.gradient` or `[0]`.
+    path: String,
+
+    /// The tolerance for numbers in the field.
+    tolerance: Tolerance,
+}
+
+impl FromStr for FieldTolerance {
+    type Err = anyhow::Error;
+
+    /// Parse `FIELD=ATOL` or `FIELD=ATOL,RTOL`, where a `FIELD` not starting with `.` or `[` is the
+    /// name of a top-level field.
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (field, tolerance) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected FIELD=ATOL or FIELD=ATOL,RTOL"))?;
+        let path = if field.is_empty() || field.starts_with(['.', '[']) {
+            field.to_string()
+        } else {
+            format!(".{field}")
+        };
+        let (absolute, relative) = tolerance.split_once(',').unwrap_or((tolerance, "0"));
+        Ok(Self {
+            path,
+            tolerance: Tolerance {
+                absolute: absolute.parse()?,
+                relative: relative.parse()?,
+            },
+        })
+    }
+}
+
+/// The outputs of a known-good session, to check the outputs of a new one against, regardless of
+/// what the eval says about them.
+#[derive(Debug, Default)]
+pub struct Golden {
+    /// The output of each successful evaluation in the known-good session.
+    outputs: HashMap<Key, Value>,
+
+    /// The tolerance for numbers outside any of the `fields`.
+    tolerance: Tolerance,
+
+    /// The tolerances for particular fields, which override `tolerance`.
+    fields: Vec<FieldTolerance>,
+}
+
+impl Golden {
+    /// Read the outputs from a `log` file, which must not have been trimmed.
+    pub fn read(
+        log: &mut impl BufRead,
+        tolerance: Tolerance,
+        fields: Vec<FieldTolerance>,
+    ) -> anyhow::Result<Self> {
+        let mut occurrences = Occurrences::default();
+        let mut outputs = HashMap::new();
+        let mut pending: Option<(Id, Key)> = None;
+        while let Some(line) = try_read_line(log)? {
+            if let Ok(entry) = serde_json::from_str::<LogMessage>(&line) {
+                pending = occurrences
+                    .key(&entry.message)
+                    .map(|key| (entry.message.id(), key));
+            } else if let Ok(entry) = serde_json::from_str::<LogResponse<EvaluateResponse>>(&line) {
+                let Some((id, key)) = pending.take_if(|(id, _)| *id == entry.response.id) else {
+                    continue;
+                };
+                if entry.response.output_ref.is_some() {
+                    bail!("evaluate response {id} has its output in a file, not the log");
+                }
+                if let (true, Some(mut output)) = (entry.response.success, entry.response.output) {
+                    decode_arrays(&mut output)?;
+                    outputs.insert(key, output);
+                }
+            }
+        }
+        if outputs.is_empty() {
+            bail!("no outputs, so the log must have been trimmed");
+        }
+        Ok(Self {
+            outputs,
+            tolerance,
+            fields,
+        })
+    }
+
+    /// Get the number of outputs in the known-good session.
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Get the tolerance for the number at `path`, from the innermost field that contains it.
+    fn tolerance(&self, path: &str) -> Tolerance {
+        self.fields
+            .iter()
+            .filter(|field| {
+                path.strip_prefix(&field.path)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+            })
+            .max_by_key(|field| field.path.len())
+            .map_or(self.tolerance, |field| field.tolerance)
+    }
+
+    /// Compare the `output` of the evaluation with the given `key` to the known-good one, or
+    /// return `None` if the known-good session has no output for it.
+    pub fn check(&self, key: &Key, output: &Value) -> Option<Result<(), String>> {
+        let expected = self.outputs.get(key)?;
+        let mut output = output.clone();
+        if let Err(err) = decode_arrays(&mut output) {
+            return Some(Err(err.to_string()));
+        }
+        let tolerance = |path: &str| self.tolerance(path);
+        Some(compare_json_by(
+            expected,
+            &output,
+            &tolerance,
+            &mut String::new(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::{FieldTolerance, Golden, Occurrences};
+    use crate::{
+        protocol::{Id, Message},
+        util::Tolerance,
+    };
+
+    const LOG: &str = r#"{ "elapsed": { "nanoseconds": 0 }, "message": {"id": 0, "kind": "start"} }
+{ "elapsed": { "nanoseconds": 1 }, "response": {"id": 0} }
+{ "elapsed": { "nanoseconds": 2 }, "message": {"id": 1, "kind": "define", "module": "m"} }
+{ "elapsed": { "nanoseconds": 3 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 4 }, "message": {"id": 2, "kind": "evaluate", "module": "m", "function": "f", "input": 1} }
+{ "elapsed": { "nanoseconds": 5 }, "response": {"id": 2, "success": true, "output": {"x": 1.0, "g": [1.0, 2.0]}} }
+{ "elapsed": { "nanoseconds": 6 }, "message": {"id": 3, "kind": "evaluate", "module": "m", "function": "f", "input": 2} }
+{ "elapsed": { "nanoseconds": 7 }, "response": {"id": 3, "success": true, "output": {"x": 2.0, "g": [3.0, 4.0]}} }
+"#;
+
+    fn evaluate(id: Id) -> Message {
+        serde_json::from_value(json!({
+            "id": id, "kind": "evaluate", "module": "m", "function": "f", "input": id,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_golden_occurrences() {
+        let fields = vec!["g=0.5".parse::<FieldTolerance>().unwrap()];
+        let golden = Golden::read(&mut Cursor::new(LOG), Tolerance::default(), fields).unwrap();
+        let mut occurrences = Occurrences::default();
+        let first = occurrences.key(&evaluate(5)).unwrap();
+        let second = occurrences.key(&evaluate(6)).unwrap();
+        let third = occurrences.key(&evaluate(7)).unwrap();
+        let output = json!({ "x": 1.0, "g": [1.25, 2.0] });
+        assert_eq!(golden.check(&first, &output), Some(Ok(())));
+        assert_eq!(
+            golden.check(&second, &output),
+            Some(Err(
+                "output.g[0] differs: expected 3.0, got 1.25".to_string()
+            )),
+        );
+        assert_eq!(golden.check(&third, &output), None);
+    }
+
+    #[test]
+    fn test_golden_field_tolerance() {
+        let fields = vec!["g=0.5".parse().unwrap(), "g[1]=0".parse().unwrap()];
+        let golden = Golden::read(&mut Cursor::new(LOG), Tolerance::default(), fields).unwrap();
+        let key = Occurrences::default().key(&evaluate(2)).unwrap();
+        assert_eq!(
+            golden.check(&key, &json!({ "x": 1.0, "g": [1.25, 2.25] })),
+            Some(Err(
+                "output.g[1] differs: expected 2.0, got 2.25".to_string()
+            )),
+        );
+    }
+
+    #[test]
+    fn test_golden_trimmed() {
+        let trimmed = LOG.lines().take(4).collect::<Vec<_>>().join("\n");
+        let result = Golden::read(&mut Cursor::new(trimmed), Tolerance::default(), Vec::new());
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::{collections::BTreeMap, fs, process::Command, thread};
+
+use crate::protocol::Host;
+
+/// Options of `docker run` that take a value as the next argument, to skip when finding the image.
+const DOCKER_RUN_OPTIONS: &[&str] = &[
+    "--add-host",
+    "--cap-add",
+    "--cpus",
+    "--cpuset-cpus",
+    "--device",
+    "--entrypoint",
+    "--env",
+    "--env-file",
+    "--gpus",
+    "--hostname",
+    "--label",
+    "--memory",
+    "--mount",
+    "--name",
+    "--network",
+    "--platform",
+    "--publish",
+    "--pull",
+    "--security-opt",
+    "--shm-size",
+    "--ulimit",
+    "--user",
+    "--volume",
+    "--workdir",
+    "-e",
+    "-h",
+    "-l",
+    "-m",
+    "-p",
+    "-u",
+    "-v",
+    "-w",
+];
+
+/// Get the image that a `docker run` command runs, or `None` for any other command.
+pub fn docker_image(cmd: &Command) -> Option<String> {
+    if cmd.get_program() != "docker" {
+        return None;
+    }
+    let mut args = cmd.get_args().map(|arg| arg.to_str());
+    if args.next()? != Some("run") {
+        return None;
+    }
+    while let Some(arg) = args.next()? {
+        if !arg.starts_with('-') {
+            return Some(arg.to_string());
+        }
+        if DOCKER_RUN_OPTIONS.contains(&arg) {
+            args.next()?;
+        }
+    }
+    None
+}
+
+/// Get the trimmed stdout of a command, if it succeeds.
+fn stdout(cmd: &mut Command) -> Option<String> {
+    let output = cmd.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string())
+}
+
+/// Get the value of the first line that starts with `key` in a file like `/proc/cpuinfo`.
+fn proc_field(path: &str, key: &str) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim().to_string())
+    })
+}
+
+/// Get the CPU model.
+fn cpu() -> Option<String> {
+    if cfg!(target_os = "linux") {
+        proc_field("/proc/cpuinfo", "model name")
+    } else if cfg!(target_os = "macos") {
+        stdout(Command::new("sysctl").args(["-n", "machdep.cpu.brand_string"]))
+    } else {
+        None
+    }
+}
+
+/// Get the total memory in bytes.
+fn memory_bytes() -> Option<u64> {
+    if cfg!(target_os = "linux") {
+        let kilobytes = proc_field("/proc/meminfo", "MemTotal")?;
+        let kilobytes: u64 = kilobytes.strip_suffix(" kB")?.parse().ok()?;
+        Some(kilobytes * 1024)
+    } else if cfg!(target_os = "macos") {
+        stdout(Command::new("sysctl").args(["-n", "hw.memsize"]))?
+            .parse()
+            .ok()
+    } else {
+        None
+    }
+}
+
+/// Get the hostname and the kernel with its release.
+#[cfg(unix)]
+fn uname() -> (Option<String>, Option<String>) {
+    match nix::sys::utsname::uname() {
+        Ok(uts) => {
+            let hostname = uts.nodename().to_str().map(String::from);
+            let kernel = match (uts.sysname().to_str(), uts.release().to_str()) {
+                (Some(name), Some(release)) => Some(format!("{name} {release}")),
+                _ => None,
+            };
+            (hostname, kernel)
+        }
+        Err(_) => (None, None),
+    }
+}
+
+/// Get the hostname and the kernel with its release.
+#[cfg(not(unix))]
+fn uname() -> (Option<String>, Option<String>) {
+    (std::env::var("COMPUTERNAME").ok(), None)
+}
+
+/// Describe this machine, along with the Docker images that the given commands would run.
+///
+/// Anything that can't be found out is left out, rather than stopping the session from running.
+pub fn host<'a>(cmds: impl IntoIterator<Item = &'a Command>) -> Host {
+    let (hostname, kernel) = uname();
+    let mut images = BTreeMap::new();
+    for image in cmds.into_iter().filter_map(docker_image) {
+        let mut inspect = Command::new("docker");
+        inspect.args(["image", "inspect", "--format", "{{.Id}}", &image]);
+        if let Some(id) = stdout(&mut inspect) {
+            images.insert(image, id);
+        }
+    }
+    Host {
+        hostname,
+        cpu: cpu(),
+        cores: thread::available_parallelism().ok().map(usize::from),
+        kernel,
+        memory_bytes: memory_bytes(),
+        gradbench: env!("CARGO_PKG_VERSION").to_string(),
+        commit: stdout(Command::new("git").args(["rev-parse", "HEAD"])),
+        images,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::docker_image;
+
+    #[test]
+    fn test_docker_image() {
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "--platform", "linux/amd64", "--rm", "--interactive"])
+            .args(["--env=FOO=1", "ghcr.io/gradbench/tool-foo:latest", "--bar"]);
+        assert_eq!(
+            docker_image(&cmd).as_deref(),
+            Some("ghcr.io/gradbench/tool-foo:latest"),
+        );
+        assert_eq!(docker_image(&Command::new("python3")), None);
+    }
+}
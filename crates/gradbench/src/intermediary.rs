@@ -1,22 +1,40 @@
 use std::{
+    borrow::Cow,
+    cell::Cell,
+    collections::HashSet,
     fs,
     io::{self, BufRead, Write},
-    process::{Child, ChildStdout},
+    path::{Component, Path, PathBuf},
+    process::{Child, ChildStderr, ExitStatus},
+    rc::Rc,
     sync::{Arc, Mutex},
+    thread,
     time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Context};
-use colored::Colorize;
+use anyhow::{anyhow, bail, Context};
+use colored::{Color, ColoredString, Colorize};
 use indexmap::IndexMap;
 use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 
 use crate::{
+    arrays::decode_arrays,
     err_fail,
+    events::{Event, EventKind, Round, Sent},
+    golden::{Golden, Occurrences},
     protocol::{
-        AnalysisResponse, DefineResponse, EvaluateResponse, Id, Message, StartResponse, Timing,
+        AnalysisResponse, BaseResponse, CancelResponse, Crash, DefineResponse, EndResponse,
+        EvaluateResponse, Footer, GoldenCheck, Host, Id, LogMessage, LogResponse, Message, Modules,
+        Nanoseconds, Phase, Protocol, Reference, Settings, StartResponse, Timing, Usage,
+    },
+    replay::Replay,
+    schema::{Schemas, Side},
+    util::{
+        compare_json, compress, container_usage, etastring, lock, nanos_duration, nanostring,
+        try_read_line, Codec, Compressed, Console, CtrlC, CtrlCHandler, Tolerance,
     },
-    util::{lock, nanostring, try_read_line, CtrlC, CtrlCHandler},
     BadOutcome,
 };
 
@@ -67,16 +85,570 @@ const WIDTH_NAME: usize = 15;
 /// Width to print the description of an input.
 const WIDTH_DESCRIPTION: usize = 15;
 
+/// The shortest time limit that an eval's hint can set, so that a tiny hint doesn't make every
+/// response time out.
+const MIN_HINT: Duration = Duration::from_secs(1);
+
+/// The default time for the tool to respond to the `"end"` message and then to exit.
+pub const DEFAULT_GRACE: Duration = Duration::from_secs(10);
+
+/// Options for running the intermediary.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The timeout for tool responses (not implemented on Windows).
+    pub timeout: Option<Duration>,
+
+    /// The timeout for the tool to respond to the `"start"` message, overriding `timeout`.
+    pub start_timeout: Option<Duration>,
+
+    /// The timeout for the tool to respond to each `"define"` message, overriding `timeout`.
+    pub define_timeout: Option<Duration>,
+
+    /// The timeout for the tool to respond to each `"evaluate"` message, overriding `timeout`.
+    pub evaluate_timeout: Option<Duration>,
+
+    /// The total time budget for the session, measured until the tool's last response.
+    pub session_timeout: Option<Duration>,
+
+    /// Whether to enforce every rule of the protocol, treating any deviation as a violation.
+    pub strict: bool,
+
+    /// Whether to also print captured stderr lines from the eval and tool to the console.
+    pub echo_stderr: bool,
+
+    /// The tolerance for comparing the outputs of other tools to those of the first tool.
+    pub tolerance: Tolerance,
+
+    /// Where to write a machine-readable stream of events.
+    pub events: Events,
+
+    /// An earlier session to estimate the progress of this one from.
+    pub progress: Option<Progress>,
+
+    /// How long the tool gets to respond to the `"end"` message and then to exit before it is
+    /// killed, if it agreed to be sent one, or [`DEFAULT_GRACE`] if not given (not implemented on
+    /// Windows). This is also how long it gets to respond to a `"cancel"` message.
+    pub grace: Option<Duration>,
+
+    /// Whether the tool's `evals.txt` says that it supports the eval, to check against the modules
+    /// it lists in its response to the `"start"` message, if any.
+    pub supported: Option<bool>,
+
+    /// The settings that were applied to the tools before they were spawned, to record in the log.
+    pub settings: Option<Settings>,
+
+    /// The host that the session runs on, to record in the log.
+    pub host: Option<Host>,
+
+    /// For each of the tools, in order, the file that `docker run` writes its container ID to if it
+    /// runs in a container, so that the resource usage of the container is recorded instead of
+    /// that of the container client.
+    pub containers: Vec<Option<Arc<tempfile::TempPath>>>,
+
+    /// Skip over lines of tool output that aren't JSON, logging them as noise instead of failing.
+    pub lenient_stdout: bool,
+
+    /// The directory shared with the eval and tools for passing inputs and outputs by reference,
+    /// if the eval and tool agree on the `"refs"` feature.
+    pub scratch: Option<PathBuf>,
+
+    /// The JSON Schemas to check the input and output of every evaluation against, if any.
+    pub schemas: Option<Arc<Schemas>>,
+
+    /// The outputs of a known-good session to check the output of every evaluation against, if
+    /// any.
+    pub golden: Option<Arc<Golden>>,
+}
+
+/// The durations of the rounds in an earlier session, for estimating the progress of a new one.
+#[derive(Clone, Debug, Default)]
+pub struct Progress {
+    /// How long the tool took to respond to each message, in order.
+    rounds: Vec<Duration>,
+}
+
+impl Progress {
+    /// Read the rounds from a `log` file, which may have been trimmed.
+    pub fn read(log: &mut impl BufRead) -> anyhow::Result<Self> {
+        let mut rounds = Vec::new();
+        let mut sent = None;
+        while let Some(line) = try_read_line(log)? {
+            if let Ok(entry) = serde_json::from_str::<LogMessage>(&line) {
+                sent = Some(entry.elapsed.nanoseconds);
+            } else if let Ok(entry) = serde_json::from_str::<LogResponse<serde_json::Value>>(&line)
+            {
+                if let Some(sent) = sent.take() {
+                    let nanoseconds = entry.elapsed.nanoseconds.saturating_sub(sent);
+                    rounds.push(nanos_duration(nanoseconds)?);
+                }
+            }
+        }
+        Ok(Self { rounds })
+    }
+}
+
+/// Where to write a machine-readable stream of events, one line of JSON per event.
+#[derive(Clone, Debug, Default)]
+pub enum Events {
+    /// Don't write any events.
+    #[default]
+    None,
+
+    /// Write events to stdout, instead of the usual human-readable output.
+    Stdout,
+
+    /// Write events to a file, alongside the usual human-readable output.
+    File(Arc<fs::File>),
+}
+
+impl Options {
+    /// Get the time limit for the tool to respond to `message`, if sent at `elapsed`.
+    ///
+    /// Also returns the phase whose limit applies, which is the session as a whole if the
+    /// session's remaining budget is smaller than the limit for the message itself.
+    fn time_limit(&self, message: &Message, elapsed: Duration) -> (Phase, Option<Duration>) {
+        let (phase, specific) = match message {
+            Message::Start { .. } => (Phase::Start, self.start_timeout),
+            Message::Define { .. } => (Phase::Define, self.define_timeout),
+            Message::Evaluate { .. } => (Phase::Evaluate, self.evaluate_timeout),
+            Message::Analysis { .. } => (Phase::Analysis, None),
+            // These aren't part of the session, so only the grace period applies.
+            Message::End { .. } => return (Phase::End, Some(self.grace())),
+            Message::Cancel { .. } => return (Phase::Cancel, Some(self.grace())),
+        };
+        // The eval knows best how long each message should take, but only within a limit that the
+        // user asked for, which gets the last word.
+        let limit = specific.or(self.timeout).map(|limit| {
+            message
+                .timeout()
+                .filter(|&s| s > 0.)
+                .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                .map_or(limit, |hint| hint.max(MIN_HINT).min(limit))
+        });
+        match self.session_timeout {
+            Some(session) => {
+                let remaining = session.saturating_sub(elapsed);
+                match limit {
+                    Some(limit) if limit <= remaining => (phase, Some(limit)),
+                    _ => (Phase::Session, Some(remaining)),
+                }
+            }
+            None => (phase, limit),
+        }
+    }
+
+    /// Get the grace period for the tool to finish up once the session is over.
+    fn grace(&self) -> Duration {
+        self.grace.unwrap_or(DEFAULT_GRACE)
+    }
+
+    /// Read the resource usage of the container of each tool that runs in one, which must be done
+    /// before the container exits.
+    fn container_usages(&self) -> Vec<Option<Usage>> {
+        (self.containers.iter())
+            .map(|cidfile| container_usage(cidfile.as_deref()?))
+            .collect()
+    }
+
+    /// Wait for the tool at position `index` to exit, with the resource usage of its `container`
+    /// in place of its own if it runs in one.
+    fn wait_for_tool(
+        &self,
+        index: usize,
+        tool: &mut Child,
+        container: Option<Usage>,
+    ) -> io::Result<(ExitStatus, Option<Usage>)> {
+        let (status, usage) = wait_with_usage(tool)?;
+        match self.containers.get(index) {
+            Some(Some(_)) => Ok((status, container)),
+            _ => Ok((status, usage)),
+        }
+    }
+}
+
+/// The source of the messages in a session.
+pub enum Eval<'a> {
+    /// An eval process.
+    Process(&'a mut Child),
+
+    /// A replay of the messages recorded in a log file.
+    Replay(Replay),
+}
+
+impl Eval<'_> {
+    /// Get the eval process, if there is one.
+    fn child(&mut self) -> Option<&mut Child> {
+        match self {
+            Eval::Process(child) => Some(child),
+            Eval::Replay(_) => None,
+        }
+    }
+
+    /// Take the channels for sending responses to the eval and receiving messages from it.
+    fn channels(&mut self) -> (Box<dyn Write>, Box<dyn BufRead>) {
+        match self {
+            Eval::Process(child) => (
+                Box::new(child.stdin.take().unwrap()),
+                Box::new(io::BufReader::new(child.stdout.take().unwrap())),
+            ),
+            Eval::Replay(replay) => {
+                let (eval_in, eval_out) = replay.split();
+                (Box::new(eval_in), Box::new(io::BufReader::new(eval_out)))
+            }
+        }
+    }
+}
+
+/// Files to write the output of a session to.
+pub struct Logs {
+    /// The log of messages and responses, which may be compressed.
+    pub log: Compressed<fs::File>,
+
+    /// Timestamped lines of stderr from the eval.
+    pub eval_stderr: fs::File,
+
+    /// Timestamped lines of stderr from the tool.
+    pub tool_stderr: fs::File,
+
+    /// The log and timestamped lines of stderr for each other tool.
+    pub others: Vec<(Compressed<fs::File>, fs::File)>,
+
+    /// Where the log for the tool and each other tool goes once the session is over.
+    paths: Vec<PathBuf>,
+}
+
+/// Get the path that a log file is written to until its session is over.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+impl Logs {
+    /// Create a log file at `path`, with files for stderr next to it.
+    ///
+    /// For instance, if `path` is `foo.jsonl` then the stderr files are `foo.eval.stderr` and
+    /// `foo.tool.stderr`. The files for the other tools are `foo.1.jsonl`, `foo.1.tool.stderr`,
+    /// `foo.2.jsonl`, `foo.2.tool.stderr`, and so on.
+    ///
+    /// If `path` ends with `.gz` or `.zst` then the logs are compressed with that codec, so for
+    /// instance `foo.jsonl.zst` comes with `foo.eval.stderr`, `foo.1.jsonl.zst`, and so on.
+    ///
+    /// Each log is written to a file with `.partial` appended to its name, like
+    /// `foo.jsonl.partial`, and only moved to its actual path by [`run`] once it is complete. A log
+    /// left behind with a `.partial` name is from a session that was cut short.
+    pub fn create(path: &Path, others: usize) -> io::Result<Self> {
+        let (base, compression) = match Codec::from_extension(path) {
+            Some(_) => (path.with_extension(""), path.extension()),
+            None => (path.to_path_buf(), None),
+        };
+        let mut paths = vec![path.to_path_buf()];
+        for i in 1..=others {
+            let mut name = base.with_extension(format!("{i}.jsonl")).into_os_string();
+            if let Some(extension) = compression {
+                name.push(".");
+                name.push(extension);
+            }
+            paths.push(PathBuf::from(name));
+        }
+        let create_log = |path: &PathBuf| {
+            compress(
+                fs::File::create(partial_path(path))?,
+                Codec::from_extension(path),
+            )
+        };
+        Ok(Self {
+            log: create_log(&paths[0])?,
+            eval_stderr: fs::File::create(base.with_extension("eval.stderr"))?,
+            tool_stderr: fs::File::create(base.with_extension("tool.stderr"))?,
+            others: paths[1..]
+                .iter()
+                .enumerate()
+                .map(|(i, path)| {
+                    Ok((
+                        create_log(path)?,
+                        fs::File::create(base.with_extension(format!("{}.tool.stderr", i + 1)))?,
+                    ))
+                })
+                .collect::<io::Result<_>>()?,
+            paths,
+        })
+    }
+
+    /// Finish writing each log, and then move it from its partial path to its actual path now that
+    /// it is complete.
+    fn finish(
+        logs: impl IntoIterator<Item = Compressed<fs::File>>,
+        paths: &[PathBuf],
+    ) -> io::Result<()> {
+        for log in logs {
+            log.finish()?;
+        }
+        for path in paths {
+            fs::rename(partial_path(path), path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe why the tool can't respond to `message`, if it is about a module or function that is
+/// missing from the `modules` the tool said it implements.
+fn unimplemented(modules: &Modules, message: &Message) -> Option<String> {
+    match message {
+        Message::Define { module, .. } | Message::Evaluate { module, .. }
+            if !modules.contains_key(module) =>
+        {
+            Some(format!("tool does not implement module {module:?}"))
+        }
+        Message::Evaluate {
+            module, function, ..
+        } => match &modules[module] {
+            Some(functions) if !functions.contains(function) => {
+                Some(format!("tool does not implement {module}::{function}"))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// State for checking that the eval and tool follow every rule of the protocol.
+#[derive(Default)]
+struct Conformance {
+    /// Whether the eval has already sent a `"start"` message.
+    started: bool,
+
+    /// The IDs of all messages the eval has sent so far.
+    ids: HashSet<Id>,
+
+    /// The IDs of all `"evaluate"` messages the eval has sent so far.
+    evaluations: HashSet<Id>,
+
+    /// The modules that the tool has successfully defined so far.
+    modules: HashSet<String>,
+
+    /// A module that the tool failed to define, after which the eval should have stopped.
+    undefined: Option<String>,
+}
+
+impl Conformance {
+    /// Check a message from the eval, returning a diagnostic if it violates the protocol.
+    fn message(&mut self, message: &Message) -> Option<String> {
+        let id = message.id();
+        let kind = message.kind();
+        if let Some(module) = &self.undefined {
+            return Some(format!(
+                "eval sent {kind} message {id} after the tool failed to define module {module:?}"
+            ));
+        }
+        if !self.ids.insert(id) {
+            return Some(format!("eval reused message ID {id} for {kind} message"));
+        }
+        if let Some(seconds) = message.timeout() {
+            if seconds <= 0. || Duration::try_from_secs_f64(seconds).is_err() {
+                return Some(format!(
+                    "eval gave {kind} message {id} an invalid timeout of {seconds} seconds"
+                ));
+            }
+        }
+        match message {
+            Message::End { .. } | Message::Cancel { .. } => {
+                return Some(format!(
+                    "eval sent {kind} message {id}, but only the intermediary sends those"
+                ));
+            }
+            Message::Start { .. } => {
+                if self.started {
+                    return Some(format!("eval sent a second start message with ID {id}"));
+                }
+                self.started = true;
+            }
+            _ if !self.started => {
+                return Some(format!(
+                    "eval sent {kind} message {id} before sending a start message"
+                ));
+            }
+            Message::Define { .. } => {}
+            Message::Evaluate {
+                module,
+                function,
+                input,
+                input_ref,
+                ..
+            } => {
+                if input.is_some() && input_ref.is_some() {
+                    return Some(format!(
+                        "eval gave evaluate message {id} both an input and an input_ref"
+                    ));
+                }
+                if !self.modules.contains(module) {
+                    return Some(format!(
+                        "eval asked to evaluate {module}::{function} in message {id} without \
+                         first successfully defining module {module:?}"
+                    ));
+                }
+                self.evaluations.insert(id);
+            }
+            Message::Analysis { of, .. } => {
+                if !self.evaluations.contains(of) {
+                    return Some(format!(
+                        "eval sent analysis message {id} of message {of}, which is not an \
+                         earlier evaluate message"
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    /// Check the ID of a response from the tool, returning a diagnostic if it doesn't match.
+    fn response(&self, message: Id, line: &str) -> Option<String> {
+        // If the response can't be parsed at all, let the usual error reporting handle it.
+        let response: BaseResponse = serde_json::from_str(line).ok()?;
+        if response.id == message {
+            None
+        } else {
+            Some(format!(
+                "tool responded with ID {} to message {message}",
+                response.id
+            ))
+        }
+    }
+
+    /// Record whether the tool successfully defined a module.
+    fn define(&mut self, module: String, success: bool) {
+        if success {
+            self.modules.insert(module);
+        } else {
+            self.undefined = Some(module);
+        }
+    }
+}
+
+/// Another tool, which is sent every message that the first tool is sent, so that its outputs can
+/// be compared to those of the first tool.
+struct Other<IT, OT, L> {
+    /// The position of this tool among the others, starting from 1.
+    index: usize,
+
+    tool_in: IT,
+    tool_out: OT,
+    log: L,
+
+    /// How this tool stopped responding, if it did.
+    outcome: Option<BadOutcome>,
+
+    /// The IDs of the `"evaluate"` messages for which this tool's output differed.
+    mismatches: Vec<Id>,
+}
+
+/// Read a line of output from a tool into `line`, like [`BufRead::read_line`].
+///
+/// If `lenient`, first skips over any lines that aren't a JSON object with an `"id"`, which is
+/// all that a response can be, writing each of them to the `log` with the time from the `clock`.
+fn read_tool_line(
+    tool_out: &mut impl BufRead,
+    (log, clock): (&mut impl Write, &mut impl FnMut() -> Duration),
+    lenient: bool,
+    line: &mut String,
+) -> io::Result<usize> {
+    loop {
+        let n = tool_out.read_line(line)?;
+        let response = || serde_json::from_str::<Value>(line).is_ok_and(|v| v.get("id").is_some());
+        if n == 0 || !lenient || response() {
+            return Ok(n);
+        }
+        writeln!(
+            log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "noise": {} }}"#,
+            clock().as_nanos(),
+            serde_json::to_string(line.trim_end_matches(['\n', '\r']))?,
+        )?;
+        line.clear();
+    }
+}
+
+/// Find the file at `path` in the `scratch` directory, which an eval or tool passed by reference.
+fn scratch_file(scratch: Option<&Path>, path: &str) -> anyhow::Result<PathBuf> {
+    let Some(scratch) = scratch else {
+        bail!("{path:?} was passed by reference, but there is no scratch directory");
+    };
+    let relative = Path::new(path);
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        bail!("{path:?} is not a relative path inside the scratch directory");
+    }
+    Ok(scratch.join(relative))
+}
+
+/// Describe the file at `path` in the `scratch` directory by its size and hash, to log in place of
+/// its contents.
+fn reference(scratch: Option<&Path>, path: &str) -> anyhow::Result<Reference> {
+    let file = scratch_file(scratch, path)?;
+    let mut reader = fs::File::open(&file).with_context(|| format!("{}", file.display()))?;
+    let mut hasher = Sha256::new();
+    let bytes = io::copy(&mut reader, &mut hasher)?;
+    Ok(Reference {
+        path: path.to_string(),
+        bytes,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+/// Read the JSON output from the file at `path` in the `scratch` directory.
+fn read_output(scratch: Option<&Path>, path: &str) -> anyhow::Result<Value> {
+    let file = scratch_file(scratch, path)?;
+    let reader = fs::File::open(&file).with_context(|| format!("{}", file.display()))?;
+    serde_json::from_reader(io::BufReader::new(reader))
+        .with_context(|| format!("invalid JSON in {}", file.display()))
+}
+
+/// Get one side of an evaluation, reading it from the scratch directory at `path` instead if it was
+/// passed by reference.
+fn resolve<'a>(
+    scratch: Option<&Path>,
+    value: Option<&'a Value>,
+    path: Option<&str>,
+) -> anyhow::Result<Option<Cow<'a, Value>>> {
+    match (value, path) {
+        (Some(value), _) => Ok(Some(Cow::Borrowed(value))),
+        (None, Some(path)) => Ok(Some(Cow::Owned(read_output(scratch, path)?))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Write a `reference` at `elapsed` to a `log`.
+fn write_reference(
+    log: &mut impl Write,
+    elapsed: Duration,
+    reference: &Reference,
+) -> anyhow::Result<()> {
+    writeln!(
+        log,
+        r#"{{ "elapsed": {{ "nanoseconds": {} }}, "reference": {} }}"#,
+        elapsed.as_nanos(),
+        serde_json::to_string(reference)?,
+    )?;
+    Ok(())
+}
+
 /// An intermediary that runs an eval and a tool, logging their output and timing their execution.
-struct Intermediary<IE, IT, OE, OT, C, T, L> {
+struct Intermediary<IE, IT, OE, OT, C, D, T, V, L> {
+    options: Options,
     outcome: Arc<Mutex<Option<BadOutcome>>>,
     eval_in: IE,
     tool_in: IT,
     eval_out: OE,
     tool_out: OT,
     clock: C,
+    deadline: D,
     out: T,
+    events: V,
     log: L,
+    others: Vec<Other<IT, OT, L>>,
+    protocol: Option<Protocol>,
 }
 
 impl<
@@ -85,10 +657,18 @@ impl<
         OE: BufRead,
         OT: BufRead,
         C: FnMut() -> Duration,
+        D: FnMut(Option<Duration>),
         T: Write,
+        V: Write,
         L: Write,
-    > Intermediary<IE, IT, OE, OT, C, T, L>
+    > Intermediary<IE, IT, OE, OT, C, D, T, V, L>
 {
+    /// Write an event that happened at `elapsed`.
+    fn event(&mut self, elapsed: Duration, kind: EventKind) -> anyhow::Result<()> {
+        Event::new(elapsed, kind).write(&mut self.events)?;
+        Ok(())
+    }
+
     /// Print left-aligned text with a fixed width, preceded by a space.
     fn print_left(&mut self, width: usize, text: &str) -> anyhow::Result<()> {
         if text.len() > width {
@@ -121,6 +701,25 @@ impl<
             .context("invalid JSON from eval")
     }
 
+    /// Remove the features from a `"start"` message if there are other tools, since they would all
+    /// have to agree on the same ones; leave any other line as it is.
+    fn offer_features(&self, eval_line: String) -> String {
+        if self.others.is_empty() {
+            return eval_line;
+        }
+        let Ok(mut message) = serde_json::from_str::<Value>(&eval_line) else {
+            return eval_line;
+        };
+        let Some(object) = message.as_object_mut() else {
+            return eval_line;
+        };
+        if object.get("kind") == Some(&json!("start")) && object.remove("features").is_some() {
+            format!("{message}\n")
+        } else {
+            eval_line
+        }
+    }
+
     /// Parse a tool response from a line of JSON.
     fn parse_response<'a, R: Deserialize<'a>>(&mut self, line: &'a str) -> anyhow::Result<R> {
         serde_json::from_str(line)
@@ -130,6 +729,186 @@ impl<
             .context("invalid JSON from tool")
     }
 
+    /// Report that the tool didn't respond to message `id`, sent at `message_time`, within `limit`.
+    fn timed_out(
+        &mut self,
+        id: Id,
+        message_time: Duration,
+        (phase, limit): (Phase, Option<Duration>),
+    ) -> anyhow::Result<()> {
+        let timeout_time = (self.clock)();
+        let nanos = (timeout_time - message_time).as_nanos();
+        writeln!(self.out, " {} {}", nanostring(nanos).dimmed(), "⧖".yellow())?;
+        let name = phase.name();
+        writeln!(self.out, "{}", format!("{name} timed out").yellow())?;
+        writeln!(
+            self.log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "timeout": {{ "phase": "{name}", "nanoseconds": {} }} }}"#,
+            timeout_time.as_nanos(),
+            limit.unwrap_or_default().as_nanos(),
+        )?;
+        let kind = EventKind::Timeout {
+            id,
+            other: None,
+            phase,
+            nanoseconds: limit.unwrap_or_default().as_nanos(),
+        };
+        self.event(timeout_time, kind)?;
+        Ok(())
+    }
+
+    /// Write the entries at the start of every log, describing the host and the tool settings.
+    fn header(&mut self) -> anyhow::Result<()> {
+        if self.options.host.is_none() && self.options.settings.is_none() {
+            return Ok(());
+        }
+        let nanoseconds = (self.clock)().as_nanos();
+        let mut lines = Vec::new();
+        if let Some(host) = &self.options.host {
+            lines.push(format!(
+                r#"{{ "elapsed": {{ "nanoseconds": {nanoseconds} }}, "host": {} }}"#,
+                serde_json::to_string(host)?,
+            ));
+        }
+        if let Some(settings) = &self.options.settings {
+            lines.push(format!(
+                r#"{{ "elapsed": {{ "nanoseconds": {nanoseconds} }}, "settings": {} }}"#,
+                serde_json::to_string(settings)?,
+            ));
+        }
+        for line in lines {
+            writeln!(self.log, "{line}")?;
+            for other in &mut self.others {
+                writeln!(other.log, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a line of output from the tool into `line`, skipping any noise if the options allow.
+    fn read_tool_line(&mut self, line: &mut String) -> io::Result<usize> {
+        let lenient = self.options.lenient_stdout;
+        let log = (&mut self.log, &mut self.clock);
+        read_tool_line(&mut self.tool_out, log, lenient, line)
+    }
+
+    /// Ask the tool to give up on message `id` after it timed out, returning whether the tool
+    /// responded to it within the grace period.
+    fn cancel(&mut self, id: Id) -> anyhow::Result<bool> {
+        let message = Message::Cancel { id };
+        let message_time = (self.clock)();
+        let (_, limit) = self.options.time_limit(&message, message_time);
+        (self.deadline)(limit);
+        // If the tool has exited then we'll find out when we try to read its response.
+        let _ = writeln!(self.tool_in, "{}", serde_json::to_string(&message)?)
+            .and_then(|()| self.tool_in.flush());
+        self.event(message_time, EventKind::Message(Sent::new(&message)))?;
+        loop {
+            let mut tool_line = String::new();
+            match self.read_tool_line(&mut tool_line) {
+                Ok(0) => return Ok(false),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => return Ok(false),
+                Err(err) => return Err(err.into()),
+            }
+            // The evaluation may have finished just too late, so skip over the response to it and
+            // wait for the one to the `"cancel"` message, which is the only one marked as such.
+            if serde_json::from_str::<CancelResponse>(&tool_line)
+                .is_ok_and(|response| response.id == id && response.cancelled)
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Send the `"end"` message with the given `id` to the tool now that the eval is done, and log
+    /// the response.
+    ///
+    /// The session is already complete, so the outcome is only affected if the tool crashes instead
+    /// of responding.
+    fn end(&mut self, line: &mut Line, id: Id) -> anyhow::Result<Option<BadOutcome>> {
+        let message = Message::End { id };
+        let message_line = serde_json::to_string(&message)?;
+        let message_time = (self.clock)();
+        writeln!(
+            self.log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "message": {} }}"#,
+            message_time.as_nanos(),
+            message_line,
+        )?;
+        line.start(&mut self.out, id)?;
+        self.print_left(WIDTH_KIND, "end")?;
+        self.out.flush()?;
+        let (phase, limit) = self.options.time_limit(&message, message_time);
+        (self.deadline)(limit);
+        // If the tool has exited then we'll find out when we try to read its response.
+        let _ = writeln!(self.tool_in, "{message_line}").and_then(|()| self.tool_in.flush());
+        self.event(message_time, EventKind::Message(Sent::new(&message)))?;
+        let mut tool_line = String::new();
+        match self.read_tool_line(&mut tool_line) {
+            Ok(0) => {
+                line.end(&mut self.out)?;
+                return Ok(Some(BadOutcome::Crash));
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                self.timed_out(id, message_time, (phase, limit))?;
+                return Ok(None);
+            }
+            Err(err) => return Err(err.into()),
+        }
+        let response_time = (self.clock)();
+        let nanos = (response_time - message_time).as_nanos();
+        write!(self.out, " {}", nanostring(nanos).dimmed())?;
+        let response: EndResponse = self.parse_response(&tool_line)?;
+        let mut round = Round::new(&message, nanos);
+        round.timings.clone_from(&response.timings);
+        if let Some(timings) = &response.timings {
+            self.print_timings(timings)?;
+        }
+        line.end(&mut self.out)?;
+        writeln!(
+            self.log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "response": {} }}"#,
+            response_time.as_nanos(),
+            tool_line.trim(),
+        )?;
+        self.log.flush()?;
+        self.event(response_time, EventKind::Round(round))?;
+        Ok(None)
+    }
+
+    /// Print a diagnostic for a protocol violation, returning the corresponding outcome.
+    fn violation(
+        &mut self,
+        line: &mut Line,
+        diagnostic: &str,
+    ) -> anyhow::Result<Option<BadOutcome>> {
+        if line.id().is_some() {
+            line.end(&mut self.out)?;
+        }
+        let error = format!("protocol violation: {diagnostic}");
+        writeln!(self.out, "{}", error.red())?;
+        let elapsed = (self.clock)();
+        self.event(elapsed, EventKind::Error { error })?;
+        Ok(Some(BadOutcome::Violation))
+    }
+
+    /// Print how far through the session the given `round` is, counting from 1, along with an
+    /// estimate of the time remaining, if there is an earlier session to go by.
+    fn print_progress(&mut self, round: usize) -> anyhow::Result<()> {
+        let Some(progress) = &self.options.progress else {
+            return Ok(());
+        };
+        let total = progress.rounds.len().max(round);
+        let remaining: Duration = progress.rounds.iter().skip(round - 1).sum();
+        let width = total.to_string().len();
+        let eta = etastring(remaining);
+        let text = format!("{round:>width$}/{total} eta {eta:>6}");
+        write!(self.out, " {}", text.dimmed())?;
+        Ok(())
+    }
+
     /// Print subtask timings.
     fn print_timings(&mut self, timings: &[Timing]) -> anyhow::Result<()> {
         let mut collected = IndexMap::new();
@@ -155,14 +934,254 @@ impl<
         Ok(())
     }
 
+    /// Send a line from the eval to each other tool that is still responding, logging the
+    /// `reference` to its input if it has one.
+    fn send_others(
+        &mut self,
+        eval_line: &str,
+        message_time: Duration,
+        reference: Option<&Reference>,
+    ) -> anyhow::Result<()> {
+        for other in self
+            .others
+            .iter_mut()
+            .filter(|other| other.outcome.is_none())
+        {
+            writeln!(
+                other.log,
+                r#"{{ "elapsed": {{ "nanoseconds": {} }}, "message": {} }}"#,
+                message_time.as_nanos(),
+                eval_line.trim(),
+            )?;
+            if let Some(reference) = reference {
+                write_reference(&mut other.log, message_time, reference)?;
+            }
+            // If the tool has exited then we'll find out when we try to read its response.
+            let _ = other
+                .tool_in
+                .write_all(eval_line.as_bytes())
+                .and_then(|()| other.tool_in.flush());
+        }
+        Ok(())
+    }
+
+    /// Read the response of each other tool that is still responding to message `id`, comparing
+    /// its output to that of the first tool if `evaluate` is true.
+    ///
+    /// Returns reports to print once the current line has ended.
+    fn receive_others(
+        &mut self,
+        (id, evaluate): (Id, bool),
+        tool_line: &str,
+        (phase, limit): (Phase, Option<Duration>),
+    ) -> anyhow::Result<Vec<ColoredString>> {
+        let scratch = self.options.scratch.as_deref();
+        let expected = if evaluate && self.others.iter().any(|other| other.outcome.is_none()) {
+            let response = serde_json::from_str::<EvaluateResponse>(tool_line)?;
+            let mut output = match response.output_ref {
+                Some(path) => Some(read_output(scratch, &path)?),
+                None => response.output,
+            };
+            if let Some(output) = &mut output {
+                decode_arrays(output)?;
+            }
+            output
+        } else {
+            None
+        };
+        let mut reports = Vec::new();
+        let mut events = Vec::new();
+        for other in self
+            .others
+            .iter_mut()
+            .filter(|other| other.outcome.is_none())
+        {
+            let name = format!("tool {}", other.index);
+            let mut other_line = String::new();
+            let log = (&mut other.log, &mut self.clock);
+            let lenient = self.options.lenient_stdout;
+            match read_tool_line(&mut other.tool_out, log, lenient, &mut other_line) {
+                Ok(0) => {
+                    // How it crashed is reported once it has been waited for.
+                    other.outcome = Some(BadOutcome::Crash);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                    other.outcome = Some(BadOutcome::Timeout);
+                    reports.push(format!("{name} timed out").yellow());
+                    let timeout_time = (self.clock)();
+                    writeln!(
+                        other.log,
+                        r#"{{ "elapsed": {{ "nanoseconds": {} }}, "timeout": {{ "phase": "{}", "nanoseconds": {} }} }}"#,
+                        timeout_time.as_nanos(),
+                        phase.name(),
+                        limit.unwrap_or_default().as_nanos(),
+                    )?;
+                    let kind = EventKind::Timeout {
+                        id,
+                        other: Some(other.index),
+                        phase,
+                        nanoseconds: limit.unwrap_or_default().as_nanos(),
+                    };
+                    events.push((timeout_time, kind));
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            }
+            let response_time = (self.clock)();
+            writeln!(
+                other.log,
+                r#"{{ "elapsed": {{ "nanoseconds": {} }}, "response": {} }}"#,
+                response_time.as_nanos(),
+                other_line.trim(),
+            )?;
+            other.log.flush()?;
+            let Some(expected) = &expected else {
+                continue;
+            };
+            let actual = match serde_json::from_str::<EvaluateResponse>(&other_line) {
+                Ok(EvaluateResponse {
+                    success: true,
+                    output: Some(actual),
+                    ..
+                }) => Ok(actual),
+                Ok(EvaluateResponse {
+                    success: true,
+                    output_ref: Some(path),
+                    ..
+                }) => reference(scratch, &path)
+                    .and_then(|reference| {
+                        write_reference(&mut other.log, response_time, &reference)?;
+                        read_output(scratch, &path)
+                    })
+                    .map_err(|err| format!("{err:#}")),
+                Ok(EvaluateResponse { error, .. }) => {
+                    Err(error.unwrap_or_else(|| "failed to evaluate".to_string()))
+                }
+                Err(err) => Err(format!("invalid JSON: {err}")),
+            };
+            let mismatch = actual
+                .and_then(|mut actual| {
+                    decode_arrays(&mut actual).map_err(|err| format!("{err:#}"))?;
+                    let tolerance = self.options.tolerance;
+                    compare_json(expected, &actual, tolerance, &mut String::new())
+                })
+                .err();
+            if let Some(mismatch) = mismatch {
+                other.mismatches.push(id);
+                reports.push(format!("{name} on message {id}: {mismatch}").red());
+                let kind = EventKind::Mismatch {
+                    id,
+                    other: other.index,
+                    error: mismatch,
+                };
+                events.push((response_time, kind));
+            }
+        }
+        for (elapsed, kind) in events {
+            self.event(elapsed, kind)?;
+        }
+        Ok(reports)
+    }
+
+    /// Print how the output of each other tool compared to that of the first tool.
+    fn print_others(&mut self) -> anyhow::Result<()> {
+        for other in &self.others {
+            let name = format!("tool {}", other.index);
+            let mismatches = other.mismatches.len();
+            match other.outcome {
+                Some(_) => {}
+                None if mismatches == 0 => writeln!(
+                    self.out,
+                    "{}",
+                    format!("{name} matched the first tool").green()
+                )?,
+                None => {
+                    let ids = other.mismatches.iter().map(|id| id.to_string());
+                    writeln!(
+                        self.out,
+                        "{}",
+                        format!(
+                            "{name} differed from the first tool on {mismatches} evaluation{}: {}",
+                            if mismatches == 1 { "" } else { "s" },
+                            ids.collect::<Vec<_>>().join(", "),
+                        )
+                        .red()
+                    )?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Print how the outputs compared to those of the known-good session.
+    fn print_golden(
+        &mut self,
+        golden: &Golden,
+        matches: usize,
+        mismatches: &[Id],
+    ) -> anyhow::Result<()> {
+        let missing = golden.len().saturating_sub(matches + mismatches.len());
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        if mismatches.is_empty() {
+            writeln!(
+                self.out,
+                "{}",
+                format!(
+                    "matched the reference on {matches} evaluation{}",
+                    plural(matches)
+                )
+                .green()
+            )?;
+        } else {
+            let ids = mismatches.iter().map(|id| id.to_string());
+            writeln!(
+                self.out,
+                "{}",
+                format!(
+                    "differed from the reference on {} evaluation{}: {}",
+                    mismatches.len(),
+                    plural(mismatches.len()),
+                    ids.collect::<Vec<_>>().join(", "),
+                )
+                .red()
+            )?;
+        }
+        if missing > 0 {
+            writeln!(
+                self.out,
+                "{}",
+                format!(
+                    "reference has {missing} output{} never compared",
+                    plural(missing)
+                )
+                .yellow()
+            )?;
+        }
+        Ok(())
+    }
+
     /// Run the intermediary, collecting miscellaneous errors via `anyhow`.
     fn run_inner(&mut self) -> anyhow::Result<Option<BadOutcome>> {
         let mut undefined = 0;
         let mut failure = 0;
         let mut invalid = 0;
+        let mut malformed = 0;
+        let mut occurrences = Occurrences::default();
+        let mut golden_matches = 0;
+        let mut golden_mismatches = Vec::new();
         let mut line = Line::new();
+        let mut conformance = self.options.strict.then(Conformance::default);
+        let mut reports = Vec::new();
+        let mut round = 0;
+        let mut modules = None;
+        let mut last_id = None;
+        self.header()?;
         while let Some(eval_line) = try_read_line(&mut self.eval_out)? {
+            round += 1;
             let message_time = (self.clock)();
+            let eval_line = self.offer_features(eval_line);
             writeln!(
                 self.log,
                 r#"{{ "elapsed": {{ "nanoseconds": {} }}, "message": {} }}"#,
@@ -170,32 +1189,74 @@ impl<
                 eval_line.trim(),
             )?;
             let message: Message = self.parse_message(&eval_line)?;
+            if let Some(diagnostic) = conformance.as_mut().and_then(|c| c.message(&message)) {
+                return self.violation(&mut line, &diagnostic);
+            }
+            let refs = self
+                .protocol
+                .as_ref()
+                .is_some_and(|p: &Protocol| p.has("refs"));
+            let golden = self.options.golden.clone().zip(occurrences.key(&message));
+            let input_reference = match &message {
+                Message::Evaluate {
+                    input_ref: Some(path),
+                    ..
+                } => {
+                    if !refs {
+                        return Err(anyhow!(
+                            "eval passed input by reference without agreeing on the refs feature"
+                        ));
+                    }
+                    let reference = reference(self.options.scratch.as_deref(), path)?;
+                    write_reference(&mut self.log, message_time, &reference)?;
+                    Some(reference)
+                }
+                _ => None,
+            };
+            last_id = last_id.max(Some(message.id()));
             match &message {
                 Message::Start { .. } => {
                     // Don't print message ID because we're still waiting for the tool to say it's
                     // ready, and e.g. if the tool is using `docker run` then it may mess with the
                     // terminal output until it actually starts.
                 }
-                Message::Define { id, module } => {
+                Message::End { .. } | Message::Cancel { .. } => {}
+                Message::Define { id, module, .. } => {
                     line.start(&mut self.out, *id)?;
                     self.print_left(WIDTH_KIND, "def")?;
                     self.print_left(WIDTH_NAME, module)?;
+                    self.print_left(WIDTH_DESCRIPTION, "")?;
+                    self.print_progress(round)?;
                 }
                 Message::Evaluate {
                     id,
                     module,
                     function,
                     input,
+                    input_ref,
                     description,
+                    ..
                 } => {
                     line.start(&mut self.out, *id)?;
                     self.print_left(WIDTH_KIND, "eval")?;
-                    let workload = match description {
-                        Some(s) => s.clone(),
-                        None => serde_json::to_string(input)?,
+                    let workload = match (description, input_ref) {
+                        (Some(s), _) | (None, Some(s)) => s.clone(),
+                        (None, None) => serde_json::to_string(input)?,
                     };
                     self.print_left(WIDTH_NAME, &format!("{module}::{function}"))?;
                     self.print_left(WIDTH_DESCRIPTION, &workload)?;
+                    self.print_progress(round)?;
+                    if let Some(schemas) = &self.options.schemas {
+                        let scratch = self.options.scratch.as_deref();
+                        let input = resolve(scratch, input.as_ref(), input_ref.as_deref())?;
+                        if let Some(Err(error)) =
+                            input.map(|input| schemas.check(module, function, Side::Input, &input))
+                        {
+                            // That's the eval's fault rather than the tool's, so stop right away.
+                            line.end(&mut self.out)?;
+                            return Err(anyhow!("eval's {error}"));
+                        }
+                    }
                 }
                 Message::Analysis {
                     id: _,
@@ -215,29 +1276,116 @@ impl<
                     }
                 }
             }
+            if line.id().is_none() {
+                for report in reports.drain(..) {
+                    writeln!(self.out, "{report}")?;
+                }
+            }
             self.out.flush()?;
+            let unimplemented = modules.as_ref().and_then(|m| unimplemented(m, &message));
+            if let (Message::Define { module, .. }, Some(modules), Some(supported)) =
+                (&message, &modules, self.options.supported)
+            {
+                if supported && !modules.contains_key(module) {
+                    reports.push(
+                        format!(
+                            "evals.txt says the tool supports this eval, but module {module:?} is \
+                             missing from its start response"
+                        )
+                        .yellow(),
+                    );
+                } else if !supported && modules.contains_key(module) {
+                    reports.push(
+                        format!(
+                            "module {module:?} is in the tool's start response, but evals.txt \
+                             says the tool does not support this eval"
+                        )
+                        .yellow(),
+                    );
+                }
+            }
             // Send the eval's response to the tool only after we've checked that it's valid JSON.
-            self.tool_in.write_all(eval_line.as_bytes())?;
-            self.tool_in.flush()?;
+            let (phase, limit) = self.options.time_limit(&message, message_time);
+            let evaluate = (message.id(), matches!(message, Message::Evaluate { .. }));
+            (self.deadline)(limit);
+            if unimplemented.is_none() {
+                self.tool_in.write_all(eval_line.as_bytes())?;
+                self.tool_in.flush()?;
+                self.event(message_time, EventKind::Message(Sent::new(&message)))?;
+            }
+            self.send_others(&eval_line, message_time, input_reference.as_ref())?;
             let mut tool_line = String::new();
-            match self.tool_out.read_line(&mut tool_line) {
+            let mut cancelled = None;
+            let read = match &unimplemented {
+                // The tool already said it can't do this, so respond for it instead of asking.
+                Some(error) => {
+                    let response = json!({ "id": message.id(), "success": false, "error": error });
+                    tool_line = format!("{response}\n");
+                    Ok(tool_line.len())
+                }
+                None => self.read_tool_line(&mut tool_line),
+            };
+            match read {
+                Ok(0) => {
+                    // The tool closed its stdout without responding, which means it has exited.
+                    if line.id().is_some() {
+                        line.end(&mut self.out)?;
+                    }
+                    return Ok(Some(BadOutcome::Crash));
+                }
                 Ok(_) => {}
                 Err(err) => {
-                    if err.kind() == io::ErrorKind::TimedOut {
-                        let timeout_time = (self.clock)();
-                        let nanos = (timeout_time - message_time).as_nanos();
-                        writeln!(self.out, " {} {}", nanostring(nanos).dimmed(), "⧖".yellow())?;
-                        return Ok(Some(BadOutcome::Timeout));
-                    } else {
+                    if err.kind() != io::ErrorKind::TimedOut {
                         return Err(err.into());
                     }
+                    let cancellable = phase == Phase::Evaluate
+                        && self
+                            .protocol
+                            .as_ref()
+                            .is_some_and(|p: &Protocol| p.has("cancel"));
+                    if !(cancellable && self.cancel(message.id())?) {
+                        self.timed_out(message.id(), message_time, (phase, limit))?;
+                        return Ok(Some(BadOutcome::Timeout));
+                    }
+                    // Let the eval know that the tool failed, and go on to the next message.
+                    let error = format!("{} timed out, so the tool cancelled it", phase.name());
+                    let response = json!({ "id": message.id(), "success": false, "error": error });
+                    tool_line = format!("{response}\n");
+                    cancelled = limit.map(|limit| (message.id(), limit));
                 }
             }
             let response_time = (self.clock)();
             let nanos = (response_time - message_time).as_nanos();
+            if let Some(diagnostic) = conformance
+                .as_ref()
+                .and_then(|c| c.response(message.id(), &tool_line))
+            {
+                return self.violation(&mut line, &diagnostic);
+            }
+            let mut round = Round::new(&message, nanos);
+            let mut negotiated = None;
+            let mut output_reference = None;
+            let mut golden_check = None;
             match message {
-                Message::Start { id, eval, .. } => {
+                Message::Start {
+                    id,
+                    eval,
+                    protocol_version,
+                    features,
+                    ..
+                } => {
                     let response: StartResponse = self.parse_response(&tool_line)?;
+                    modules.clone_from(&response.modules);
+                    let agreed =
+                        Protocol::negotiate(protocol_version, features.as_deref(), &response);
+                    negotiated = Some(agreed.map(|mut agreed| {
+                        // Only the first tool would get the messages for these features.
+                        if !self.others.is_empty() {
+                            agreed.features.clear();
+                        }
+                        agreed
+                    }));
+                    round.tool.clone_from(&response.tool);
                     // OK now that we know the tool won't do anything weird with the terminal.
                     line.start(&mut self.out, id)?;
                     self.print_left(WIDTH_KIND, "start")?;
@@ -249,13 +1397,18 @@ impl<
                     }
                     line.end(&mut self.out)?;
                 }
-                Message::Define { .. } => {
-                    self.print_left(WIDTH_DESCRIPTION, "")?;
+                Message::Define { module, .. } => {
                     write!(self.out, " {}", nanostring(nanos).dimmed())?;
                     let response: DefineResponse = self.parse_response(&tool_line)?;
+                    round.success = Some(response.success);
+                    round.timings.clone_from(&response.timings);
+                    round.error.clone_from(&response.error);
                     if !response.success {
                         undefined += 1;
                     }
+                    if let Some(conformance) = &mut conformance {
+                        conformance.define(module, response.success);
+                    }
                     if let Some(timings) = response.timings {
                         self.print_timings(&timings)?;
                     }
@@ -268,24 +1421,78 @@ impl<
                         }
                     }
                 }
-                Message::Evaluate { .. } => {
+                Message::Evaluate {
+                    id,
+                    module,
+                    function,
+                    ..
+                } => {
                     write!(self.out, " {}", nanostring(nanos).dimmed())?;
                     let response: EvaluateResponse = self.parse_response(&tool_line)?;
+                    round.success = Some(response.success);
+                    round.timings.clone_from(&response.timings);
+                    round.error.clone_from(&response.error);
                     if !response.success {
                         failure += 1;
                     }
                     if let Some(timings) = response.timings {
                         self.print_timings(&timings)?;
                     }
+                    if let Some(path) = &response.output_ref {
+                        if !refs {
+                            line.end(&mut self.out)?;
+                            return Err(anyhow!(
+                                "tool passed output by reference without agreeing on the refs \
+                                 feature"
+                            ));
+                        }
+                        output_reference = Some(reference(self.options.scratch.as_deref(), path)?);
+                    }
                     if response.success {
                         if let Some(error) = response.error {
                             line.end(&mut self.out)?;
                             writeln!(self.out, "{}", error.red())?;
                             return Err(anyhow!("tool reported success but gave an error"));
-                        } else if response.output.is_none() {
+                        } else if response.output.is_none() && response.output_ref.is_none() {
                             line.end(&mut self.out)?;
                             return Err(anyhow!("tool reported success but gave no output"));
                         }
+                        let output = if self.options.schemas.is_some() || golden.is_some() {
+                            let scratch = self.options.scratch.as_deref();
+                            let path = response.output_ref.as_deref();
+                            resolve(scratch, response.output.as_ref(), path)?
+                        } else {
+                            None
+                        };
+                        if let (Some(schemas), Some(output)) = (&self.options.schemas, &output) {
+                            // Flag the output now, before the eval even analyzes it.
+                            if let Err(error) =
+                                schemas.check(&module, &function, Side::Output, output)
+                            {
+                                malformed += 1;
+                                self.print_status(false)?;
+                                line.end(&mut self.out)?;
+                                writeln!(self.out, "{}", error.red())?;
+                            }
+                        }
+                        if let (Some((golden, key)), Some(output)) = (&golden, &output) {
+                            if let Some(checked) = golden.check(key, output) {
+                                match &checked {
+                                    Ok(()) => golden_matches += 1,
+                                    Err(error) => {
+                                        golden_mismatches.push(id);
+                                        reports.push(
+                                            format!("reference on message {id}: {error}").red(),
+                                        );
+                                    }
+                                }
+                                golden_check = Some(GoldenCheck {
+                                    of: id,
+                                    valid: checked.is_ok(),
+                                    error: checked.err(),
+                                });
+                            }
+                        }
                     } else {
                         self.print_status(false)?;
                         line.end(&mut self.out)?;
@@ -297,6 +1504,19 @@ impl<
                 Message::Analysis { .. } => {
                     let _: AnalysisResponse = self.parse_response(&tool_line)?;
                 }
+                Message::End { .. } => {
+                    let _: EndResponse = self.parse_response(&tool_line)?;
+                }
+                Message::Cancel { .. } => {
+                    let _: BaseResponse = self.parse_response(&tool_line)?;
+                }
+            }
+            self.event(response_time, EventKind::Round(round))?;
+            reports.extend(self.receive_others(evaluate, &tool_line, (phase, limit))?);
+            if line.id().is_none() {
+                for report in reports.drain(..) {
+                    writeln!(self.out, "{report}")?;
+                }
             }
             self.out.flush()?;
             // Send the tool's response to the eval only after we've checked that it's valid JSON.
@@ -306,14 +1526,91 @@ impl<
                 response_time.as_nanos(),
                 tool_line.trim(),
             )?;
+            if let Some(reference) = &output_reference {
+                write_reference(&mut self.log, response_time, reference)?;
+            }
+            if let Some(check) = &golden_check {
+                writeln!(
+                    self.log,
+                    r#"{{ "elapsed": {{ "nanoseconds": {} }}, "golden": {} }}"#,
+                    response_time.as_nanos(),
+                    serde_json::to_string(check)?,
+                )?;
+            }
+            if unimplemented.is_some() {
+                let (id, _) = evaluate;
+                writeln!(
+                    self.log,
+                    r#"{{ "elapsed": {{ "nanoseconds": {} }}, "unimplemented": {id} }}"#,
+                    response_time.as_nanos(),
+                )?;
+            }
+            if let Some((id, limit)) = cancelled {
+                writeln!(
+                    self.log,
+                    r#"{{ "elapsed": {{ "nanoseconds": {} }}, "cancelled": {{ "phase": "{}", "nanoseconds": {} }} }}"#,
+                    response_time.as_nanos(),
+                    phase.name(),
+                    limit.as_nanos(),
+                )?;
+                let kind = EventKind::Cancel {
+                    id,
+                    phase,
+                    nanoseconds: limit.as_nanos(),
+                };
+                self.event(response_time, kind)?;
+            }
+            match negotiated {
+                Some(Ok(agreed)) => {
+                    writeln!(
+                        self.log,
+                        r#"{{ "elapsed": {{ "nanoseconds": {} }}, "protocol": {} }}"#,
+                        response_time.as_nanos(),
+                        serde_json::to_string(&agreed)?,
+                    )?;
+                    self.protocol = Some(agreed);
+                }
+                Some(Err(diagnostic)) => {
+                    return Err(anyhow!("eval and tool are incompatible: {diagnostic}"));
+                }
+                None => {}
+            }
+            // Flush after every round so that little is lost if we are killed.
+            self.log.flush()?;
             self.eval_in.write_all(tool_line.as_bytes())?;
             self.eval_in.flush()?;
         }
-        if undefined > 0 {
+        if !reports.is_empty() && line.id().is_some() {
+            line.end(&mut self.out)?;
+        }
+        for report in reports {
+            writeln!(self.out, "{report}")?;
+        }
+        self.print_others()?;
+        if let Some(golden) = self.options.golden.clone() {
+            self.print_golden(&golden, golden_matches, &golden_mismatches)?;
+        }
+        if self
+            .protocol
+            .as_ref()
+            .is_some_and(|protocol| protocol.has("end"))
+        {
+            let id = last_id.map_or(0, |id| id.saturating_add(1));
+            if let Some(outcome) = self.end(&mut line, id)? {
+                return Ok(Some(outcome));
+            }
+        }
+        // Outputs that don't even match the schema make the rest of the session meaningless.
+        if malformed > 0 {
+            Ok(Some(BadOutcome::Malformed))
+        } else if undefined > 0 {
             Ok(Some(BadOutcome::Undefined))
         } else if failure > 0 {
             Ok(Some(BadOutcome::Failure))
-        } else if invalid > 0 {
+        } else if invalid > 0
+            || !golden_mismatches.is_empty()
+            || self.others.iter().any(|other| !other.mismatches.is_empty())
+        {
             Ok(Some(BadOutcome::Invalid))
         } else {
             Ok(None)
@@ -330,18 +1627,20 @@ impl<
             Ok(None) => Ok(()),
             Ok(Some(outcome)) => Err(outcome),
             Err(err) => {
-                let _ = writeln!(self.out, "{}", format!("{err:#}").red());
+                let error = format!("{err:#}");
+                let _ = writeln!(self.out, "{}", error.red());
+                let elapsed = (self.clock)();
+                let _ = self.event(elapsed, EventKind::Error { error });
                 Err(BadOutcome::Error)
             }
         }
     }
 }
 
-/// Handle Ctrl-C by killing the eval and tool and setting a status flag.
+/// Handle Ctrl-C by killing the eval and tools and setting a status flag.
 fn handle_ctrlc<'a>(
-    ctrl_c: &'a mut CtrlC,
-    eval: &mut Child,
-    tool: &mut Child,
+    ctrl_c: &'a CtrlC,
+    children: &[&Child],
     outcome: Arc<Mutex<Option<BadOutcome>>>,
 ) -> anyhow::Result<CtrlCHandler<'a>> {
     #[cfg(not(unix))]
@@ -350,104 +1649,535 @@ fn handle_ctrlc<'a>(
     }
     #[cfg(unix)]
     {
-        use nix::{sys::signal, unistd};
-        let eval_pid = unistd::Pid::from_raw(eval.id().try_into()?);
-        let tool_pid = unistd::Pid::from_raw(tool.id().try_into()?);
+        use nix::unistd;
+        let pids = children
+            .iter()
+            .map(|child| Ok(unistd::Pid::from_raw(child.id().try_into()?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
         Ok(ctrl_c.handle(Box::new(move || {
-            if let Ok(pgid) = unistd::getpgid(Some(eval_pid)) {
-                let _ = signal::killpg(pgid, signal::Signal::SIGKILL);
-            }
-            if let Ok(pgid) = unistd::getpgid(Some(tool_pid)) {
-                let _ = signal::killpg(pgid, signal::Signal::SIGKILL);
+            for &pid in &pids {
+                kill_group(pid);
             }
             *lock(&outcome) = Some(BadOutcome::Interrupt);
         })))
     }
 }
 
-/// Return a reader that times out after a given duration, if possible.
-fn timeout_reader(reader: ChildStdout, timeout: Option<Duration>) -> impl io::Read {
-    #[cfg(unix)]
-    {
-        timeout_readwrite::TimeoutReader::new(reader, timeout)
+/// Kill the process group of a process, if it still exists.
+#[cfg(unix)]
+fn kill_group(pid: nix::unistd::Pid) {
+    use nix::{sys::signal, unistd};
+    if let Ok(pgid) = unistd::getpgid(Some(pid)) {
+        let _ = signal::killpg(pgid, signal::Signal::SIGKILL);
+    }
+}
+
+/// Kill the process group of each of the `tools` that is still around once the `grace` period is
+/// over, unless that is cancelled by dropping the returned sender first.
+///
+/// The returned thread says whether the grace period ran out.
+#[cfg(unix)]
+fn kill_after<'a>(
+    grace: Duration,
+    tools: impl IntoIterator<Item = &'a Child>,
+) -> (std::sync::mpsc::Sender<()>, thread::JoinHandle<bool>) {
+    use std::sync::mpsc;
+    let pids: Vec<_> = tools
+        .into_iter()
+        .filter_map(|child| child.id().try_into().ok())
+        .map(nix::unistd::Pid::from_raw)
+        .collect();
+    let (cancel, cancelled) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        if cancelled.recv_timeout(grace) != Err(mpsc::RecvTimeoutError::Timeout) {
+            return false;
+        }
+        for pid in pids {
+            kill_group(pid);
+        }
+        true
+    });
+    (cancel, handle)
+}
+
+/// A reader that times out if no data is available by a deadline, which can be changed between
+/// reads.
+struct DeadlineReader<R> {
+    /// The underlying reader.
+    reader: R,
+
+    /// The deadline for the next read, if any.
+    deadline: Rc<Cell<Option<Instant>>>,
+}
+
+#[cfg(unix)]
+impl<R: io::Read + std::os::fd::AsFd> io::Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use nix::{
+            errno::Errno,
+            poll::{poll, PollFd, PollFlags, PollTimeout},
+        };
+        if let Some(deadline) = self.deadline.get() {
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+                let mut fds = [PollFd::new(self.reader.as_fd(), PollFlags::POLLIN)];
+                match poll(&mut fds, timeout) {
+                    Ok(0) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "tool timed out"));
+                    }
+                    Ok(_) => break,
+                    Err(Errno::EINTR) => continue,
+                    Err(errno) => return Err(errno.into()),
+                }
+            }
+        }
+        self.reader.read(buf)
+    }
+}
+
+#[cfg(windows)]
+impl<R: io::Read> io::Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+/// Spawn a thread to copy lines of stderr into a file, each stamped with the elapsed time.
+///
+/// If `echo` is given, each line is also printed to the `console`, labeled in the given color.
+fn capture_stderr(
+    stderr: ChildStderr,
+    mut file: fs::File,
+    start: Instant,
+    (mut console, echo): (Console, Option<(String, Color)>),
+) -> thread::JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        for result in io::BufReader::new(stderr).lines() {
+            let line = result?;
+            let elapsed = start.elapsed();
+            writeln!(
+                file,
+                "{}.{:09} {line}",
+                elapsed.as_secs(),
+                elapsed.subsec_nanos(),
+            )?;
+            if let Some((name, color)) = &echo {
+                // Build the whole line first so that it is written under a single lock.
+                let prefix = format!("{name}:").color(*color).bold();
+                console.write_all(format!("{prefix} {line}\n").as_bytes())?;
+            }
+        }
+        file.flush()
+    })
+}
+
+/// Convert a `timeval` from the C library to nanoseconds.
+#[cfg(unix)]
+fn timeval_nanoseconds(timeval: nix::libc::timeval) -> Nanoseconds {
+    let seconds = u128::try_from(timeval.tv_sec).unwrap_or_default();
+    let microseconds = u128::try_from(timeval.tv_usec).unwrap_or_default();
+    Nanoseconds {
+        nanoseconds: seconds * 1_000_000_000 + microseconds * 1000,
+    }
+}
+
+/// Wait for a child process to exit, returning its exit status and resource usage.
+///
+/// This reaps the process directly, so `child.wait()` must not be called afterward.
+#[cfg(unix)]
+fn wait_with_usage(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    use nix::libc;
+    let pid = libc::pid_t::try_from(child.id()).map_err(io::Error::other)?;
+    let mut status = 0;
+    // SAFETY: `rusage` is a plain C struct, for which all zeros is a valid value.
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: both pointers are valid for writes for the duration of the call.
+        if unsafe { libc::wait4(pid, &mut status, 0, &mut rusage) } != -1 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
     }
+    // Linux reports the peak resident set size in kilobytes, but macOS reports it in bytes.
+    let max_rss = u64::try_from(rusage.ru_maxrss).unwrap_or_default();
+    let max_rss_bytes = if cfg!(target_os = "macos") {
+        max_rss
+    } else {
+        max_rss * 1024
+    };
+    let usage = Usage {
+        max_rss_bytes,
+        user: timeval_nanoseconds(rusage.ru_utime),
+        system: timeval_nanoseconds(rusage.ru_stime),
+        voluntary_context_switches: u64::try_from(rusage.ru_nvcsw).ok(),
+        involuntary_context_switches: u64::try_from(rusage.ru_nivcsw).ok(),
+    };
+    Ok((ExitStatus::from_raw(status), Some(usage)))
+}
+
+/// Wait for a child process to exit; resource usage is not collected on Windows.
+#[cfg(windows)]
+fn wait_with_usage(child: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    Ok((child.wait()?, None))
+}
+
+/// Describe how a tool exited.
+fn crash(status: ExitStatus) -> Crash {
+    #[cfg(unix)]
+    let signal = std::os::unix::process::ExitStatusExt::signal(&status);
     #[cfg(windows)]
-    {
-        reader
+    let signal = None;
+    Crash {
+        status: status.code(),
+        signal,
+    }
+}
+
+/// Record how a tool exited at the end of its log, along with its resource usage if known.
+///
+/// The `other` is the position of the tool among the other tools, if it is not the first tool.
+fn record_exit(
+    (out, events): (&mut impl Write, &mut impl Write),
+    log: &mut impl Write,
+    start: Instant,
+    other: Option<usize>,
+    crashed: bool,
+    waited: io::Result<(ExitStatus, Option<Usage>)>,
+) -> anyhow::Result<Crash> {
+    let (status, usage) = waited?;
+    if crashed {
+        let crash = crash(status);
+        let name = match other {
+            Some(index) => format!("tool {index}"),
+            None => "tool".to_string(),
+        };
+        writeln!(out, "{}", format!("{name} crashed: {crash}").red())?;
+        writeln!(
+            log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "crash": {} }}"#,
+            start.elapsed().as_nanos(),
+            serde_json::to_string(&crash)?,
+        )?;
+        Event::new(start.elapsed(), EventKind::Crash { other, crash }).write(events)?;
+    }
+    if let Some(usage) = usage {
+        writeln!(
+            log,
+            r#"{{ "elapsed": {{ "nanoseconds": {} }}, "usage": {} }}"#,
+            start.elapsed().as_nanos(),
+            serde_json::to_string(&usage)?,
+        )?;
     }
+    Ok(crash(status))
 }
 
-/// Run an eval and a tool together, returning the outcome.
-fn run_helper(
-    ctrl_c: &mut CtrlC,
-    log: impl Write,
-    eval: &mut Child,
-    tool: &mut Child,
-    timeout: Option<Duration>,
+/// Write the footer that marks the end of a complete log.
+fn write_footer(log: &mut impl Write, start: Instant, footer: &Footer) -> anyhow::Result<()> {
+    writeln!(
+        log,
+        r#"{{ "elapsed": {{ "nanoseconds": {} }}, "footer": {} }}"#,
+        start.elapsed().as_nanos(),
+        serde_json::to_string(footer)?,
+    )?;
+    log.flush()?;
+    Ok(())
+}
+
+/// Run an eval and some tools together, returning the outcome.
+///
+/// The first of the `tools` is the one whose responses are sent to the eval.
+#[allow(clippy::too_many_arguments)]
+fn run_helper<L: Write>(
+    ctrl_c: &CtrlC,
+    console: Console,
+    mut log: L,
+    mut other_logs: Vec<L>,
+    stderr: Option<(fs::File, fs::File, Vec<fs::File>)>,
+    mut eval: Eval,
+    tools: &mut [Child],
+    options: &Options,
 ) -> Result<(), BadOutcome> {
     let outcome_mutex = Arc::new(Mutex::new(None));
-    let ctrl_c_handler = match handle_ctrlc(ctrl_c, eval, tool, Arc::clone(&outcome_mutex)) {
+    let mut children: Vec<&Child> = tools.iter().collect();
+    if let Some(child) = eval.child() {
+        children.push(child);
+    }
+    let ctrl_c_handler = match handle_ctrlc(ctrl_c, &children, Arc::clone(&outcome_mutex)) {
         Ok(handler) => handler,
         Err(err) => {
             err_fail(err);
             return Err(BadOutcome::Error);
         }
     };
+    let (tool, others) = tools.split_first_mut().expect("at least one tool");
+    let (mut out, mut events): (Box<dyn Write + '_>, Box<dyn Write + '_>) = match &options.events {
+        Events::None => (Box::new(console.clone()), Box::new(io::sink())),
+        Events::Stdout => (Box::new(io::sink()), Box::new(console.clone())),
+        Events::File(file) => (Box::new(console.clone()), Box::new(&**file)),
+    };
     let start = Instant::now();
-    let outcome = Intermediary {
+    let deadline = Rc::new(Cell::new(None));
+    let mut captures = Vec::new();
+    if let Some((eval_file, tool_file, other_files)) = stderr {
+        let echo = |name: &str, color| {
+            let echo = options.echo_stderr.then(|| (name.to_string(), color));
+            (console.clone(), echo)
+        };
+        if let Some(eval_stderr) = eval.child().and_then(|child| child.stderr.take()) {
+            let echo = echo("eval", Color::Blue);
+            captures.push(capture_stderr(eval_stderr, eval_file, start, echo));
+        }
+        if let Some(tool_stderr) = tool.stderr.take() {
+            let echo = echo("tool", Color::Magenta);
+            captures.push(capture_stderr(tool_stderr, tool_file, start, echo));
+        }
+        for (i, (other, file)) in others.iter_mut().zip(other_files).enumerate() {
+            if let Some(other_stderr) = other.stderr.take() {
+                let echo = echo(&format!("tool {}", i + 1), Color::Magenta);
+                captures.push(capture_stderr(other_stderr, file, start, echo));
+            }
+        }
+    }
+    let (eval_in, eval_out) = eval.channels();
+    let mut intermediary = Intermediary {
+        options: options.clone(),
         outcome: outcome_mutex,
-        eval_in: eval.stdin.take().unwrap(),
+        eval_in,
         tool_in: tool.stdin.take().unwrap(),
-        eval_out: io::BufReader::new(eval.stdout.take().unwrap()),
-        tool_out: io::BufReader::new(timeout_reader(tool.stdout.take().unwrap(), timeout)),
+        eval_out,
+        tool_out: io::BufReader::new(DeadlineReader {
+            reader: tool.stdout.take().unwrap(),
+            deadline: Rc::clone(&deadline),
+        }),
         clock: || start.elapsed(),
-        out: io::stdout(),
-        log,
-    }
-    .run();
+        deadline: |limit: Option<Duration>| {
+            deadline.set(limit.and_then(|limit| Instant::now().checked_add(limit)));
+        },
+        out: &mut out,
+        events: &mut events,
+        log: &mut log,
+        others: others
+            .iter_mut()
+            .zip(&mut other_logs)
+            .enumerate()
+            .map(|(i, (other, log))| Other {
+                index: i + 1,
+                tool_in: other.stdin.take().unwrap(),
+                tool_out: io::BufReader::new(DeadlineReader {
+                    reader: other.stdout.take().unwrap(),
+                    deadline: Rc::clone(&deadline),
+                }),
+                log,
+                outcome: None,
+                mismatches: Vec::new(),
+            })
+            .collect(),
+        protocol: None,
+    };
+    let outcome = intermediary.run();
+    // The tools are still running until their stdin is closed, so their containers are still there.
+    let mut container_usages = options.container_usages().into_iter();
+    let ended = (intermediary.protocol.as_ref()).is_some_and(|protocol| protocol.has("end"));
+    let other_outcomes: Vec<_> = intermediary
+        .others
+        .iter()
+        .map(|other| other.outcome)
+        .collect();
+    drop(intermediary);
     drop(ctrl_c_handler);
     // If fail due to a timeout, the tool may still be running. Kill
-    // its process group to ensure that we will not be hanging in a
-    // wait() call in main.rs.
+    // its process group to ensure that we will not be hanging in the
+    // wait() call below.
     #[cfg(unix)]
-    if let Err(BadOutcome::Timeout) = outcome {
-        use nix::{sys::signal, unistd};
-        if let Ok(tool_id) = tool.id().try_into() {
-            let tool_pid = unistd::Pid::from_raw(tool_id);
-            if let Ok(pgid) = unistd::getpgid(Some(tool_pid)) {
-                let _ = signal::killpg(pgid, signal::Signal::SIGKILL);
+    {
+        let timed_out = std::iter::once((&*tool, outcome.err()))
+            .chain(others.iter().zip(other_outcomes.iter().copied()))
+            .filter(|(_, outcome)| *outcome == Some(BadOutcome::Timeout));
+        for (child, _) in timed_out {
+            if let Ok(id) = child.id().try_into() {
+                kill_group(nix::unistd::Pid::from_raw(id));
             }
         }
     }
+    // The eval and tools should exit soon now that their stdin has been closed, but if a tool that
+    // agreed to be sent the end message doesn't, kill it once its grace period is over so that we
+    // don't hang waiting for it. Other tools never agreed to exit in time, so wait for them.
+    #[cfg(unix)]
+    let killer =
+        ended.then(|| kill_after(options.grace(), std::iter::once(&*tool).chain(&*others)));
+    let eval_wait = eval.child().map(|child| child.wait()).transpose();
+    let tool_wait = options.wait_for_tool(0, tool, container_usages.next().flatten());
+    let other_waits: Vec<_> = (others.iter_mut().enumerate())
+        .map(|(i, other)| {
+            let container = container_usages.next().flatten();
+            options.wait_for_tool(i + 1, other, container)
+        })
+        .collect();
+    #[cfg(unix)]
+    if let Some((cancel, killer)) = killer {
+        drop(cancel);
+        if killer.join().unwrap_or(false) {
+            let grace = options.grace().as_secs_f64();
+            let message = format!("killed tools still running after the grace period of {grace}s");
+            let _ = writeln!(out, "{}", message.yellow());
+        }
+    }
+    // They close their stderr when they exit, so this shouldn't block for long.
+    for capture in captures {
+        if let Ok(Err(err)) = capture.join() {
+            let _ = writeln!(out, "{}", format!("error capturing stderr: {err}").red());
+        }
+    }
+    let mut eval_exit = None;
+    let mut tool_exit = None;
+    let mut other_exits = vec![None; other_logs.len()];
+    let waited = eval_wait.map_err(anyhow::Error::from).and_then(|status| {
+        eval_exit = status.map(crash);
+        let crashed = outcome == Err(BadOutcome::Crash);
+        let streams = (&mut out, &mut events);
+        tool_exit = Some(record_exit(
+            streams, &mut log, start, None, crashed, tool_wait,
+        )?);
+        let others = other_logs.iter_mut().zip(&other_outcomes).zip(other_waits);
+        for (i, ((log, outcome), waited)) in others.enumerate() {
+            let crashed = *outcome == Some(BadOutcome::Crash);
+            let streams = (&mut out, &mut events);
+            other_exits[i] = Some(record_exit(
+                streams,
+                log,
+                start,
+                Some(i + 1),
+                crashed,
+                waited,
+            )?);
+        }
+        Ok(())
+    });
+    let outcome = match waited {
+        Ok(()) => outcome,
+        Err(err) => {
+            let _ = writeln!(out, "{}", format!("{err:#}").red());
+            outcome.and(Err(BadOutcome::Error))
+        }
+    };
+    let name = |outcome: Result<(), BadOutcome>| outcome.map_or_else(<&str>::from, |()| "success");
+    let footer = Footer {
+        outcome: name(outcome).to_string(),
+        eval: eval_exit.clone(),
+        tool: tool_exit,
+    };
+    let mut footed = write_footer(&mut log, start, &footer);
+    let others = other_logs.iter_mut().zip(other_outcomes).zip(other_exits);
+    for ((log, other_outcome), tool) in others {
+        let footer = Footer {
+            outcome: name(other_outcome.map_or(outcome, Err)).to_string(),
+            eval: eval_exit.clone(),
+            tool,
+        };
+        footed = footed.and(write_footer(log, start, &footer));
+    }
+    let outcome = match footed {
+        Ok(()) => outcome,
+        Err(err) => {
+            let _ = writeln!(out, "{}", format!("{err:#}").red());
+            outcome.and(Err(BadOutcome::Error))
+        }
+    };
+    let end = EventKind::End {
+        outcome: name(outcome),
+    };
+    let _ = Event::new(start.elapsed(), end).write(&mut events);
     outcome
 }
 
-/// Run an eval and a tool together, returning the outcome.
+/// Run an eval and some tools together, returning the outcome once all have exited.
+///
+/// Every message from the eval is sent to all the `tools`, but only the responses of the first
+/// tool are sent back to the eval; the outputs of the other tools are compared to those of the
+/// first. The outcome is that of the first tool.
+///
+/// If there are logs, the resource usage of each tool is recorded at the end of its log, followed
+/// by a footer with the outcome and exit statuses, after which each log is moved into place.
+///
+/// What would be printed to stdout is written to the `console` instead, such as a buffer when
+/// several sessions run at once, along with any stderr that is echoed.
 pub fn run(
-    ctrl_c: &mut CtrlC,
-    log: Option<fs::File>,
-    eval: &mut Child,
-    tool: &mut Child,
-    timeout: Option<Duration>,
+    ctrl_c: &CtrlC,
+    console: Console,
+    logs: Option<Logs>,
+    eval: Eval,
+    tools: &mut [Child],
+    options: &Options,
 ) -> Result<(), BadOutcome> {
-    match log {
-        Some(file) => run_helper(ctrl_c, file, eval, tool, timeout),
-        None => run_helper(ctrl_c, &mut io::sink(), eval, tool, timeout),
+    match logs {
+        Some(Logs {
+            mut log,
+            eval_stderr,
+            tool_stderr,
+            others,
+            paths,
+        }) => {
+            let (mut other_logs, other_stderr): (Vec<_>, _) = others.into_iter().unzip();
+            let outcome = run_helper(
+                ctrl_c,
+                console,
+                &mut log,
+                other_logs.iter_mut().collect(),
+                Some((eval_stderr, tool_stderr, other_stderr)),
+                eval,
+                tools,
+                options,
+            );
+            if let Err(err) = Logs::finish(std::iter::once(log).chain(other_logs), &paths) {
+                err_fail(anyhow::Error::from(err).context("failed to finish log"));
+                return outcome.and(Err(BadOutcome::Error));
+            }
+            outcome
+        }
+        None => {
+            let other_logs = tools.iter().skip(1).map(|_| io::sink()).collect();
+            run_helper(
+                ctrl_c,
+                console,
+                io::sink(),
+                other_logs,
+                None,
+                eval,
+                tools,
+                options,
+            )
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
+        fs,
         io::{self, Write},
+        mem::take,
         sync::{Arc, Mutex},
         time::Duration,
     };
 
     use goldenfile::Mint;
     use pretty_assertions::assert_eq;
+    use serde_json::{json, Value};
 
-    use crate::intermediary::{BadOutcome, Intermediary};
+    use crate::{
+        golden::Golden,
+        intermediary::{BadOutcome, Intermediary, Options, Other, Progress},
+        protocol::{Host, Message, Phase, Settings},
+        replay::Replay,
+        schema::Schemas,
+        util::Tolerance,
+    };
 
     fn write_goldenfile(name: &str, bytes: &[u8]) {
         let mut mint = Mint::new("src/outputs");
@@ -462,6 +2192,7 @@ mod tests {
         let mut duration = Duration::ZERO;
         let mut increment = Duration::ZERO;
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
@@ -472,8 +2203,12 @@ mod tests {
                 duration += increment;
                 duration
             },
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -486,14 +2221,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/start_names.jsonl");
         let tool_out = include_str!("inputs/tool/start_names.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         let result = intermediary.run();
         write_goldenfile("start_names.txt", &intermediary.out);
@@ -507,6 +2247,7 @@ mod tests {
         let mut duration = Duration::ZERO;
         let mut increment = Duration::ZERO;
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
@@ -517,8 +2258,12 @@ mod tests {
                 duration += increment;
                 duration
             },
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         let result = intermediary.run();
         write_goldenfile("define_timings.txt", &intermediary.out);
@@ -528,14 +2273,19 @@ mod tests {
     #[test]
     fn test_intermediary_invalid_json_eval() {
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: r#"{ "id": 0,"#.as_bytes(),
             tool_out: "".as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         let result = intermediary.run();
         write_goldenfile("invalid_json_eval.txt", &intermediary.out);
@@ -545,14 +2295,19 @@ mod tests {
     #[test]
     fn test_intermediary_invalid_json_tool() {
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: r#"{ "id": 0, "kind": "start" }"#.as_bytes(),
             tool_out: r#"{ "id": 0,"#.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         let result = intermediary.run();
         write_goldenfile("invalid_json_tool.txt", &intermediary.out);
@@ -564,14 +2319,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/define_error.jsonl");
         let tool_out = include_str!("inputs/tool/define_error.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -584,14 +2344,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/define_success_error.jsonl");
         let tool_out = include_str!("inputs/tool/define_success_error.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -604,14 +2369,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/evaluate.jsonl");
         let tool_out = include_str!("inputs/tool/evaluate_error.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -624,14 +2394,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/evaluate.jsonl");
         let tool_out = include_str!("inputs/tool/evaluate_failure_no_error.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -644,14 +2419,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/evaluate.jsonl");
         let tool_out = include_str!("inputs/tool/evaluate_success_error.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -664,14 +2444,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/evaluate.jsonl");
         let tool_out = include_str!("inputs/tool/evaluate_success_no_output.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -684,14 +2469,19 @@ mod tests {
         let eval_out = include_str!("inputs/eval/evaluate_null_output.jsonl");
         let tool_out = include_str!("inputs/tool/evaluate_null_output.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: tool_out.as_bytes(),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
@@ -728,18 +2518,983 @@ mod tests {
         let eval_out = include_str!("inputs/eval/intermediary_timeout.jsonl");
         let tool_out = include_str!("inputs/tool/intermediary_timeout.jsonl");
         let mut intermediary = Intermediary {
+            options: Options::default(),
             outcome: Arc::new(Mutex::new(None)),
             eval_in: io::sink(),
             tool_in: io::sink(),
             eval_out: eval_out.as_bytes(),
             tool_out: ReadTimeout(tool_out.as_bytes()),
             clock: || Duration::ZERO,
+            deadline: |_| {},
             out: Vec::new(),
+            events: io::sink(),
             log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
         };
         colored::control::set_override(false);
         let result = intermediary.run();
         write_goldenfile("timeout.txt", &intermediary.out);
         assert_eq!(result, Err(BadOutcome::Timeout));
     }
+
+    #[test]
+    fn test_intermediary_crash() {
+        let eval_out = include_str!("inputs/eval/crash.jsonl");
+        let tool_out = include_str!("inputs/tool/crash.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("crash.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Crash));
+    }
+
+    #[test]
+    fn test_intermediary_events() {
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let mut duration = Duration::ZERO;
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || {
+                duration += Duration::from_millis(1);
+                duration
+            },
+            deadline: |_| {},
+            out: io::sink(),
+            events: Vec::new(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("events.jsonl", &intermediary.events);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+    }
+
+    #[test]
+    fn test_intermediary_progress() {
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let log = include_str!("inputs/log/progress.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                progress: Some(Progress::read(&mut log.as_bytes()).unwrap()),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("progress.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+    }
+
+    #[test]
+    fn test_intermediary_protocol() {
+        let eval_out = include_str!("inputs/eval/protocol.jsonl");
+        let tool_out = include_str!("inputs/tool/protocol.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("protocol.jsonl", &intermediary.log);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_intermediary_protocol_incompatible() {
+        let eval_out = include_str!("inputs/eval/protocol.jsonl");
+        let tool_out = include_str!("inputs/tool/protocol_incompatible.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("protocol_incompatible.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Error));
+    }
+
+    #[test]
+    fn test_intermediary_end() {
+        let eval_out = include_str!("inputs/eval/end.jsonl");
+        let tool_out = include_str!("inputs/tool/end.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: Vec::new(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("end.jsonl", &intermediary.log);
+        assert_eq!(result, Ok(()));
+        let sent = String::from_utf8(intermediary.tool_in).unwrap();
+        assert_eq!(sent.lines().last(), Some(r#"{"kind":"end","id":2}"#));
+    }
+
+    /// Like [`ReadTimeout`], but times out at each blank line and then carries on after it.
+    struct ReadTimeouts<T>(T);
+
+    impl<T: io::Read> io::Read for ReadTimeouts<T> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<T: io::BufRead> io::BufRead for ReadTimeouts<T> {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            if self.0.fill_buf()?.first() == Some(&b'\n') {
+                self.0.consume(1);
+                return Err(io::Error::new(io::ErrorKind::TimedOut, ""));
+            }
+            self.0.fill_buf()
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.0.consume(amt);
+        }
+    }
+
+    #[test]
+    fn test_intermediary_cancel() {
+        let eval_out = include_str!("inputs/eval/cancel.jsonl");
+        let tool_out = include_str!("inputs/tool/cancel.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: Vec::new(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: ReadTimeouts(tool_out.as_bytes()),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("cancel.jsonl", &intermediary.log);
+        assert_eq!(result, Err(BadOutcome::Failure));
+        let sent = String::from_utf8(intermediary.tool_in).unwrap();
+        let cancel = r#"{"kind":"cancel","id":2}"#;
+        assert_eq!(sent.lines().filter(|&line| line == cancel).count(), 1);
+    }
+
+    #[test]
+    fn test_intermediary_cancel_late() {
+        let eval_out = include_str!("inputs/eval/cancel.jsonl");
+        let tool_out = include_str!("inputs/tool/cancel_late.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                timeout: Some(Duration::from_secs(1)),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: Vec::new(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: ReadTimeouts(tool_out.as_bytes()),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        assert_eq!(result, Err(BadOutcome::Failure));
+        let received = String::from_utf8(intermediary.eval_in).unwrap();
+        let responses: Vec<Value> = received
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(responses[2]["success"], json!(false));
+        assert_eq!(
+            responses[3],
+            json!({ "id": 3, "success": true, "output": 16 })
+        );
+    }
+
+    #[test]
+    fn test_intermediary_noise() {
+        let eval_out = include_str!("inputs/eval/evaluate.jsonl");
+        let tool_out = include_str!("inputs/tool/noise.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                lenient_stdout: true,
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("noise.jsonl", &intermediary.log);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_intermediary_noise_strict() {
+        let eval_out = include_str!("inputs/eval/evaluate.jsonl");
+        let tool_out = include_str!("inputs/tool/noise.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        assert_eq!(intermediary.run(), Err(BadOutcome::Error));
+    }
+
+    #[test]
+    fn test_intermediary_refs() {
+        let scratch = tempfile::tempdir().unwrap();
+        fs::write(scratch.path().join("input-2.json"), "42").unwrap();
+        fs::write(scratch.path().join("output-2.json"), "1764").unwrap();
+        let eval_out = include_str!("inputs/eval/refs.jsonl");
+        let tool_out = include_str!("inputs/tool/refs.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                scratch: Some(scratch.path().to_path_buf()),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("refs.jsonl", &intermediary.log);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_intermediary_refs_not_agreed() {
+        let scratch = tempfile::tempdir().unwrap();
+        fs::write(scratch.path().join("input-2.json"), "42").unwrap();
+        let eval_out = include_str!("inputs/eval/refs.jsonl");
+        let tool_out = include_str!("inputs/tool/evaluate_error.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                scratch: Some(scratch.path().to_path_buf()),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("refs_not_agreed.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Error));
+    }
+
+    #[test]
+    fn test_intermediary_schemas() {
+        let dir = tempfile::tempdir().unwrap();
+        let schemas = json!({
+            "bar": { "input": { "type": "number" }, "output": { "type": "number" } },
+            "baz": {
+                "output": {
+                    "type": "object",
+                    "properties": { "yournumber": { "type": "string" } },
+                },
+            },
+        });
+        fs::write(dir.path().join("foo.json"), schemas.to_string()).unwrap();
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                schemas: Some(Arc::new(Schemas::read(dir.path()).unwrap())),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("schemas.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Malformed));
+    }
+
+    #[test]
+    fn test_intermediary_schemas_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let schemas = json!({ "bar": { "output": { "type": "number" } } });
+        fs::write(dir.path().join("foo.json"), schemas.to_string()).unwrap();
+        let eval_out = r#"{ "id": 0, "kind": "start" }
+{ "id": 1, "kind": "define", "module": "foo" }
+{ "id": 2, "kind": "evaluate", "module": "foo", "function": "bar", "input": 1 }
+{ "id": 3, "kind": "evaluate", "module": "foo", "function": "bar", "input": 2 }
+"#;
+        let tool_out = r#"{ "id": 0 }
+{ "id": 1, "success": true }
+{ "id": 2, "success": false, "error": "out of memory" }
+{ "id": 3, "success": true, "output": "4" }
+"#;
+        let mut intermediary = Intermediary {
+            options: Options {
+                schemas: Some(Arc::new(Schemas::read(dir.path()).unwrap())),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        assert_eq!(intermediary.run(), Err(BadOutcome::Malformed));
+    }
+
+    #[test]
+    fn test_intermediary_schemas_eval() {
+        let dir = tempfile::tempdir().unwrap();
+        let schemas = json!({ "bar": { "input": { "type": "integer" } } });
+        fs::write(dir.path().join("foo.json"), schemas.to_string()).unwrap();
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                schemas: Some(Arc::new(Schemas::read(dir.path()).unwrap())),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        assert_eq!(result, Err(BadOutcome::Error));
+        let out = String::from_utf8(intermediary.out).unwrap();
+        assert!(out
+            .ends_with("eval's input doesn't match schema: 3.14159 is not of type \"integer\"\n"));
+    }
+
+    fn golden() -> Golden {
+        let log = include_str!("inputs/log/golden.jsonl");
+        Golden::read(&mut log.as_bytes(), Tolerance::default(), Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn test_intermediary_golden() {
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                golden: Some(Arc::new(golden())),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("golden.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+    }
+
+    #[test]
+    fn test_intermediary_golden_log() {
+        let eval_out = include_str!("inputs/eval/contributing_md_example.jsonl");
+        let tool_out = include_str!("inputs/tool/contributing_md_example.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                golden: Some(Arc::new(golden())),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("golden.jsonl", &intermediary.log);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+    }
+
+    #[test]
+    fn test_intermediary_modules() {
+        let eval_out = include_str!("inputs/eval/modules.jsonl");
+        let tool_out = include_str!("inputs/tool/modules.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                supported: Some(false),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("modules.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Failure));
+        let log = String::from_utf8_lossy(&intermediary.log);
+        let notes: Vec<_> = log
+            .lines()
+            .filter(|line| line.contains(r#""unimplemented""#))
+            .collect();
+        assert_eq!(
+            notes,
+            [r#"{ "elapsed": { "nanoseconds": 0 }, "unimplemented": 4 }"#]
+        );
+    }
+
+    #[test]
+    fn test_intermediary_header() {
+        let eval_out = include_str!("inputs/eval/define_timings.jsonl");
+        let tool_out = include_str!("inputs/tool/define_timings.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                host: Some(Host {
+                    hostname: Some("localhost".to_string()),
+                    cores: Some(4),
+                    gradbench: "0.0.0".to_string(),
+                    ..Default::default()
+                }),
+                settings: Some(Settings {
+                    cpus: Some("0-3".to_string()),
+                    env: [("OMP_NUM_THREADS".to_string(), "1".to_string())].into(),
+                }),
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: io::sink(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: vec![Other {
+                index: 1,
+                tool_in: io::sink(),
+                tool_out: tool_out.as_bytes(),
+                log: Vec::new(),
+                outcome: None,
+                mismatches: Vec::new(),
+            }],
+            protocol: None,
+        };
+        let result = intermediary.run();
+        write_goldenfile("header.jsonl", &intermediary.log);
+        assert_eq!(result, Ok(()));
+        // Every log gets the same header.
+        let log = String::from_utf8(intermediary.log).unwrap();
+        let other = String::from_utf8(take(&mut intermediary.others[0].log)).unwrap();
+        assert_eq!(
+            log.lines().take(2).collect::<Vec<_>>(),
+            other.lines().take(2).collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn test_intermediary_fan_out() {
+        let eval_out = include_str!("inputs/eval/fan_out.jsonl");
+        let tool_out = include_str!("inputs/tool/fan_out.jsonl");
+        let others = [
+            include_str!("inputs/tool/fan_out_differs.jsonl"),
+            include_str!("inputs/tool/fan_out_crash.jsonl"),
+        ];
+        let mut intermediary = Intermediary {
+            options: Options {
+                tolerance: Tolerance {
+                    absolute: 1e-6,
+                    relative: 1e-6,
+                },
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: others
+                .iter()
+                .enumerate()
+                .map(|(i, tool_out)| Other {
+                    index: i + 1,
+                    tool_in: io::sink(),
+                    tool_out: tool_out.as_bytes(),
+                    log: io::sink(),
+                    outcome: None,
+                    mismatches: Vec::new(),
+                })
+                .collect(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("fan_out.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+        let outcomes: Vec<_> = intermediary
+            .others
+            .iter()
+            .map(|other| other.outcome)
+            .collect();
+        assert_eq!(outcomes, [None, Some(BadOutcome::Crash)]);
+    }
+
+    #[test]
+    fn test_intermediary_fan_out_arrays() {
+        let eval_out = include_str!("inputs/eval/fan_out.jsonl");
+        let tool_out = include_str!("inputs/tool/fan_out.jsonl");
+        let other_out = include_str!("inputs/tool/fan_out_arrays.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: vec![Other {
+                index: 1,
+                tool_in: io::sink(),
+                tool_out: other_out.as_bytes(),
+                log: io::sink(),
+                outcome: None,
+                mismatches: Vec::new(),
+            }],
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("fan_out_arrays.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+        assert_eq!(intermediary.others[0].mismatches, [4]);
+    }
+
+    #[test]
+    fn test_intermediary_fan_out_features() {
+        let eval_out = r#"{ "id": 0, "kind": "start", "features": ["arrays", "refs"] }
+"#;
+        let tool_out = r#"{ "id": 0, "features": ["end", "cancel"] }
+"#;
+        let other_out = r#"{ "id": 0 }
+"#;
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: Vec::new(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: Vec::new(),
+            others: vec![Other {
+                index: 1,
+                tool_in: Vec::new(),
+                tool_out: other_out.as_bytes(),
+                log: Vec::new(),
+                outcome: None,
+                mismatches: Vec::new(),
+            }],
+            protocol: None,
+        };
+        let result = intermediary.run();
+        assert_eq!(result, Ok(()));
+        let start = "{\"id\":0,\"kind\":\"start\"}\n";
+        // Without any agreed features, the tool isn't sent an end message either.
+        assert_eq!(String::from_utf8_lossy(&intermediary.tool_in), start);
+        assert_eq!(
+            String::from_utf8_lossy(&intermediary.others[0].tool_in),
+            start
+        );
+        let log = String::from_utf8_lossy(&intermediary.log);
+        assert!(log.contains(r#""protocol": {"version":1,"features":[]}"#));
+    }
+
+    #[test]
+    fn test_intermediary_replay() {
+        let log = include_str!("inputs/log/replay.jsonl");
+        let tool_out = include_str!("inputs/tool/replay.jsonl");
+        let tolerance = Tolerance {
+            absolute: 1e-6,
+            relative: 1e-6,
+        };
+        let replay = Replay::new(&mut log.as_bytes(), tolerance).unwrap();
+        let (eval_in, eval_out) = replay.split();
+        let mut intermediary = Intermediary {
+            options: Options::default(),
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in,
+            tool_in: io::sink(),
+            eval_out: io::BufReader::new(eval_out),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("replay.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Invalid));
+    }
+
+    #[test]
+    fn test_intermediary_strict_response_id() {
+        let eval_out = include_str!("inputs/eval/strict_response_id.jsonl");
+        let tool_out = include_str!("inputs/tool/strict_response_id.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                strict: true,
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("strict_response_id.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Violation));
+    }
+
+    #[test]
+    fn test_intermediary_strict_duplicate_id() {
+        let eval_out = include_str!("inputs/eval/strict_duplicate_id.jsonl");
+        let tool_out = include_str!("inputs/tool/strict_duplicate_id.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                strict: true,
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("strict_duplicate_id.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Violation));
+    }
+
+    #[test]
+    fn test_intermediary_strict_analysis_of() {
+        let eval_out = include_str!("inputs/eval/strict_analysis_of.jsonl");
+        let tool_out = include_str!("inputs/tool/strict_analysis_of.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                strict: true,
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("strict_analysis_of.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Violation));
+    }
+
+    #[test]
+    fn test_intermediary_strict_after_undefined() {
+        let eval_out = include_str!("inputs/eval/strict_after_undefined.jsonl");
+        let tool_out = include_str!("inputs/tool/strict_after_undefined.jsonl");
+        let mut intermediary = Intermediary {
+            options: Options {
+                strict: true,
+                ..Default::default()
+            },
+            outcome: Arc::new(Mutex::new(None)),
+            eval_in: io::sink(),
+            tool_in: io::sink(),
+            eval_out: eval_out.as_bytes(),
+            tool_out: tool_out.as_bytes(),
+            clock: || Duration::ZERO,
+            deadline: |_| {},
+            out: Vec::new(),
+            events: io::sink(),
+            log: io::sink(),
+            others: Vec::new(),
+            protocol: None,
+        };
+        colored::control::set_override(false);
+        let result = intermediary.run();
+        write_goldenfile("strict_after_undefined.txt", &intermediary.out);
+        assert_eq!(result, Err(BadOutcome::Violation));
+    }
+
+    #[test]
+    fn test_options_time_limit_session() {
+        let options = Options {
+            timeout: Some(Duration::from_secs(10)),
+            define_timeout: Some(Duration::from_secs(60)),
+            session_timeout: Some(Duration::from_secs(100)),
+            ..Default::default()
+        };
+        let define = Message::Define {
+            id: 1,
+            module: "foo".to_string(),
+            timeout: None,
+        };
+        let analysis = Message::Analysis {
+            id: 2,
+            of: 1,
+            valid: true,
+            error: None,
+        };
+        assert_eq!(
+            options.time_limit(&define, Duration::from_secs(30)),
+            (Phase::Define, Some(Duration::from_secs(60))),
+        );
+        assert_eq!(
+            options.time_limit(&define, Duration::from_secs(70)),
+            (Phase::Session, Some(Duration::from_secs(30))),
+        );
+        assert_eq!(
+            options.time_limit(&analysis, Duration::from_secs(70)),
+            (Phase::Analysis, Some(Duration::from_secs(10))),
+        );
+    }
+
+    #[test]
+    fn test_options_time_limit_hint() {
+        let evaluate = |timeout| Message::Evaluate {
+            id: 2,
+            module: "foo".to_string(),
+            function: "bar".to_string(),
+            input: None,
+            input_ref: None,
+            description: None,
+            timeout,
+        };
+        let capped = Options {
+            timeout: Some(Duration::from_secs(10)),
+            ..Default::default()
+        };
+        assert_eq!(
+            capped.time_limit(&evaluate(Some(2.5)), Duration::ZERO),
+            (Phase::Evaluate, Some(Duration::from_millis(2500))),
+        );
+        assert_eq!(
+            capped.time_limit(&evaluate(Some(60.)), Duration::ZERO),
+            (Phase::Evaluate, Some(Duration::from_secs(10))),
+        );
+        assert_eq!(
+            Options::default().time_limit(&evaluate(Some(60.)), Duration::ZERO),
+            (Phase::Evaluate, None),
+        );
+        assert_eq!(
+            capped.time_limit(&evaluate(Some(-1.)), Duration::ZERO),
+            (Phase::Evaluate, Some(Duration::from_secs(10))),
+        );
+        assert_eq!(
+            capped.time_limit(&evaluate(Some(0.)), Duration::ZERO),
+            (Phase::Evaluate, Some(Duration::from_secs(10))),
+        );
+        assert_eq!(
+            Options::default().time_limit(&evaluate(Some(0.)), Duration::ZERO),
+            (Phase::Evaluate, None),
+        );
+        assert_eq!(
+            capped.time_limit(&evaluate(Some(1e-9)), Duration::ZERO),
+            (Phase::Evaluate, Some(Duration::from_secs(1))),
+        );
+    }
 }
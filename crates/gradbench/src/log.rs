@@ -1,5 +1,10 @@
 use crate::{
-    protocol::{EvaluateResponse, LogMessage, LogResponse, Message, StartResponse},
+    protocol::{
+        is_note, Crash, EvaluateResponse, Footer, Host, LogCancelled, LogCrash, LogFooter,
+        LogGolden, LogHost, LogMessage, LogNoise, LogProtocol, LogReference, LogResponse,
+        LogSettings, LogTimeout, LogUnimplemented, LogUsage, Message, Phase, Protocol, Settings,
+        StartResponse, Timeout, Usage,
+    },
     util::{try_read_line, InOut},
 };
 
@@ -14,6 +19,10 @@ impl InOut<anyhow::Result<()>> for Trim {
     fn run(self, read: impl io::Read, mut out: impl io::Write) -> anyhow::Result<()> {
         let input = &mut io::BufReader::new(read);
         while let Some(line) = try_read_line(input)? {
+            if is_note(&line) {
+                write!(out, "{}", line)?;
+                continue;
+            }
             let mut message: LogMessage = serde_json::from_str(&line)?;
             match message.message {
                 Message::Evaluate {
@@ -21,28 +30,49 @@ impl InOut<anyhow::Result<()>> for Trim {
                     module,
                     function,
                     input: _,
+                    input_ref,
                     description,
+                    timeout,
                 } => {
-                    if let Some(response_line) = try_read_line(input)? {
-                        let mut response: LogResponse<EvaluateResponse> =
-                            serde_json::from_str(&response_line)?;
+                    let mut notes = Vec::new();
+                    let response_line = read_past_notes(input, &mut notes)?;
+                    if response_line.is_some() || !notes.is_empty() {
                         message.message = Message::Evaluate {
                             id,
                             module,
                             function,
                             input: None,
+                            input_ref,
                             description,
+                            timeout,
                         };
-                        response.response.output = None;
                         writeln!(out, "{}", serde_json::to_string(&message)?)?;
-                        writeln!(out, "{}", serde_json::to_string(&response)?)?;
                     } else {
                         write!(out, "{}", line)?;
                     }
+                    for note in notes {
+                        write!(out, "{}", note)?;
+                    }
+                    if let Some(response_line) = response_line {
+                        if Ending::parse(&response_line).is_some() {
+                            // There's no response to trim if the tool timed out or crashed.
+                            write!(out, "{}", response_line)?;
+                        } else {
+                            let mut response: LogResponse<EvaluateResponse> =
+                                serde_json::from_str(&response_line)?;
+                            response.response.output = None;
+                            writeln!(out, "{}", serde_json::to_string(&response)?)?;
+                        }
+                    }
                 }
                 _ => {
                     write!(out, "{}", line)?;
-                    if let Some(response_line) = try_read_line(input)? {
+                    let mut notes = Vec::new();
+                    let response_line = read_past_notes(input, &mut notes)?;
+                    for note in notes {
+                        write!(out, "{}", note)?;
+                    }
+                    if let Some(response_line) = response_line {
                         write!(out, "{}", response_line)?;
                     }
                 }
@@ -52,6 +82,180 @@ impl InOut<anyhow::Result<()>> for Trim {
     }
 }
 
+/// An entry in a log file that ends a session early, in place of a response.
+enum Ending {
+    /// The tool timed out.
+    Timeout(Timeout),
+
+    /// The tool crashed.
+    Crash(Crash),
+}
+
+impl Ending {
+    /// Parse a line of a log file as an ending, along with its timestamp in nanoseconds.
+    fn parse(line: &str) -> Option<(u128, Self)> {
+        if let Ok(entry) = serde_json::from_str::<LogTimeout>(line) {
+            Some((entry.elapsed.nanoseconds, Ending::Timeout(entry.timeout)))
+        } else if let Ok(entry) = serde_json::from_str::<LogCrash>(line) {
+            Some((entry.elapsed.nanoseconds, Ending::Crash(entry.crash)))
+        } else {
+            None
+        }
+    }
+
+    /// Describe the ending in a human-readable way.
+    fn describe(&self) -> String {
+        match self {
+            Ending::Timeout(timeout) => {
+                let limit = nanostring(timeout.nanoseconds);
+                let limit = limit.trim();
+                match timeout.phase {
+                    Phase::Session => {
+                        format!("Tool ran out of session time, which was limited to {limit}.")
+                    }
+                    phase => format!(
+                        "Tool did not respond to {} message within the time limit of {limit}.",
+                        phase.name()
+                    ),
+                }
+            }
+            Ending::Crash(crash) => format!("Tool crashed: {crash}."),
+        }
+    }
+}
+
+/// The entries that the intermediary adds to a log file alongside the messages and responses.
+#[derive(Default)]
+struct Notes {
+    /// The host that the session ran on.
+    host: Option<Host>,
+
+    /// The settings applied to the tool.
+    settings: Option<Settings>,
+
+    /// The protocol that the eval and tool agreed on.
+    protocol: Option<Protocol>,
+
+    /// The resource usage of the tool.
+    usage: Option<Usage>,
+
+    /// The number of evaluations that the tool cancelled after they timed out.
+    cancelled: usize,
+
+    /// The number of messages that the intermediary responded to on the tool's behalf, because the
+    /// tool doesn't implement their module or function.
+    unimplemented: usize,
+
+    /// The number of lines that the tool printed to stdout which were not JSON.
+    noise: usize,
+
+    /// The number of files passed by reference instead of inline, and their total size.
+    references: (usize, u64),
+
+    /// The number of outputs that matched those of a known-good session, and that differed.
+    golden: (usize, usize),
+
+    /// How the session ended, which is missing if the log is incomplete.
+    footer: Option<Footer>,
+}
+
+/// Read the next line of a log that is not one of the [`Notes`], keeping the lines of any notes
+/// skipped over.
+fn read_past_notes(
+    input: &mut impl io::BufRead,
+    skipped: &mut Vec<String>,
+) -> anyhow::Result<Option<String>> {
+    while let Some(line) = try_read_line(input)? {
+        if !is_note(&line) {
+            return Ok(Some(line));
+        }
+        skipped.push(line);
+    }
+    Ok(None)
+}
+
+/// Read the next line of a log that is not one of the [`Notes`], storing any notes skipped over.
+fn read_entry_line(
+    input: &mut impl io::BufRead,
+    notes: &mut Notes,
+) -> anyhow::Result<Option<String>> {
+    while let Some(line) = try_read_line(input)? {
+        if let Ok(entry) = serde_json::from_str::<LogHost>(&line) {
+            notes.host = Some(entry.host);
+        } else if let Ok(entry) = serde_json::from_str::<LogSettings>(&line) {
+            notes.settings = Some(entry.settings);
+        } else if let Ok(entry) = serde_json::from_str::<LogProtocol>(&line) {
+            notes.protocol = Some(entry.protocol);
+        } else if serde_json::from_str::<LogCancelled>(&line).is_ok() {
+            notes.cancelled += 1;
+        } else if serde_json::from_str::<LogUnimplemented>(&line).is_ok() {
+            notes.unimplemented += 1;
+        } else if serde_json::from_str::<LogNoise>(&line).is_ok() {
+            notes.noise += 1;
+        } else if let Ok(entry) = serde_json::from_str::<LogReference>(&line) {
+            notes.references.0 += 1;
+            notes.references.1 += entry.reference.bytes;
+        } else if let Ok(entry) = serde_json::from_str::<LogGolden>(&line) {
+            if entry.golden.valid {
+                notes.golden.0 += 1;
+            } else {
+                notes.golden.1 += 1;
+            }
+        } else if let Ok(entry) = serde_json::from_str::<LogUsage>(&line) {
+            notes.usage = Some(entry.usage);
+        } else if let Ok(entry) = serde_json::from_str::<LogFooter>(&line) {
+            notes.footer = Some(entry.footer);
+        } else {
+            return Ok(Some(line));
+        }
+    }
+    Ok(None)
+}
+
+/// Return a human-readable string for the given number of bytes.
+fn bytestring(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024. * 1024.))
+}
+
+/// Print the host that a session ran on, for [`Summary`].
+fn write_host(out: &mut impl io::Write, host: &Host) -> io::Result<()> {
+    let Host {
+        hostname,
+        cpu,
+        cores,
+        kernel,
+        memory_bytes,
+        gradbench,
+        commit,
+        images,
+    } = host;
+    let label = |name: &str| format!("{name:>11}").bold();
+    match (hostname, kernel) {
+        (Some(hostname), Some(kernel)) => {
+            writeln!(out, "{}: {hostname} ({kernel})", label("host"))?
+        }
+        (Some(name), None) | (None, Some(name)) => writeln!(out, "{}: {name}", label("host"))?,
+        (None, None) => {}
+    }
+    match (cpu, cores) {
+        (Some(cpu), Some(cores)) => writeln!(out, "{}: {cpu} × {cores}", label("cpu"))?,
+        (Some(cpu), None) => writeln!(out, "{}: {cpu}", label("cpu"))?,
+        (None, Some(cores)) => writeln!(out, "{}: {cores} cores", label("cpu"))?,
+        (None, None) => {}
+    }
+    if let Some(bytes) = memory_bytes {
+        writeln!(out, "{}: {}", label("memory"), bytestring(*bytes))?;
+    }
+    match commit {
+        Some(commit) => writeln!(out, "{}: {gradbench} at {commit}", label("gradbench"))?,
+        None => writeln!(out, "{}: {gradbench}", label("gradbench"))?,
+    }
+    for (image, id) in images {
+        writeln!(out, "{}: {image} {id}", label("image"))?;
+    }
+    Ok(())
+}
+
 pub struct Summary;
 
 impl InOut<anyhow::Result<()>> for Summary {
@@ -66,10 +270,13 @@ impl InOut<anyhow::Result<()>> for Summary {
         let mut num_valid = 0;
         let mut num_invalid = 0;
         let mut interrupted = false;
+        let mut ending = None;
+        let mut late = None;
+        let mut notes = Notes::default();
         let mut elapsed_ns = 0;
 
         // First read the Start message and get the val name.
-        if let Some(line) = try_read_line(input)? {
+        if let Some(line) = read_entry_line(input, &mut notes)? {
             let message: LogMessage = serde_json::from_str(&line)?;
             match message.message {
                 Message::Start { eval, config, .. } => {
@@ -83,18 +290,24 @@ impl InOut<anyhow::Result<()>> for Summary {
         }
 
         // Then read the response for the tool name.
-        if let Some(line) = try_read_line(input)? {
-            let response: LogResponse<StartResponse> = serde_json::from_str(&line)?;
-            tool_name = response.response.tool;
-            tool_config = response.response.config;
+        if let Some(line) = read_entry_line(input, &mut notes)? {
+            if let Some((nanoseconds, entry)) = Ending::parse(&line) {
+                elapsed_ns = nanoseconds;
+                ending = Some(entry);
+            } else {
+                let response: LogResponse<StartResponse> = serde_json::from_str(&line)?;
+                tool_name = response.response.tool;
+                tool_config = response.response.config;
+            }
         }
 
         // Then run through the rest of the messages and collect
         // statistics. Currently we do not do anything with responses,
         // except for noting their 'elapsed' field.
-        while let Some(line) = try_read_line(input)? {
+        while let Some(line) = read_entry_line(input, &mut notes)? {
             let message: LogMessage = serde_json::from_str(&line)?;
             elapsed_ns = message.elapsed.nanoseconds;
+            let is_end = matches!(message.message, Message::End { .. });
             match message.message {
                 Message::Evaluate { .. } => {
                     num_evaluation += 1;
@@ -109,7 +322,17 @@ impl InOut<anyhow::Result<()>> for Summary {
                 _ => (),
             }
             // Skip the response.
-            if let Some(response_line) = try_read_line(input)? {
+            if let Some(response_line) = read_entry_line(input, &mut notes)? {
+                if let Some((nanoseconds, entry)) = Ending::parse(&response_line) {
+                    elapsed_ns = nanoseconds;
+                    // The outcome is decided by the time of the `"end"` message, and only a crash
+                    // can change it after that.
+                    match entry {
+                        Ending::Timeout(_) if is_end => late = Some(entry),
+                        _ => ending = Some(entry),
+                    }
+                    break;
+                }
                 let response: LogResponse<serde_json::Value> =
                     serde_json::from_str(&response_line)?;
                 elapsed_ns = response.elapsed.nanoseconds;
@@ -118,6 +341,8 @@ impl InOut<anyhow::Result<()>> for Summary {
                 break;
             }
         }
+        // A timeout or crash ends the loop early, before the entries at the end of the log.
+        read_entry_line(input, &mut notes)?;
 
         if let Some(eval) = eval_name {
             writeln!(out, "{:>11}: {}", "eval".blue().bold(), eval)?
@@ -136,13 +361,103 @@ impl InOut<anyhow::Result<()>> for Summary {
         if let Some(config) = tool_config {
             writeln!(out, "{:>11}: {}", "config".magenta().bold(), config)?
         }
+        if let Some(host) = &notes.host {
+            write_host(&mut out, host)?;
+        }
+        if let Some(Settings { cpus, env }) = &notes.settings {
+            if let Some(cpus) = cpus {
+                writeln!(out, "{:>11}: {cpus}", "cpus".bold())?;
+            }
+            for (name, value) in env {
+                writeln!(out, "{:>11}: {name}={value}", "env".bold())?;
+            }
+        }
+        if let Some(Protocol { version, features }) = &notes.protocol {
+            write!(out, "{:>11}: version {version}", "protocol".bold())?;
+            if !features.is_empty() {
+                write!(out, " with {}", features.join(", "))?;
+            }
+            writeln!(out)?
+        }
 
         writeln!(out, "{:>11}: {}", "evaluations".bold(), num_evaluation)?;
         writeln!(out, "{:>11}: {}", "valid".bold(), num_valid)?;
         writeln!(out, "{:>11}: {}", "invalid".bold(), num_invalid)?;
+        if notes.cancelled > 0 {
+            writeln!(out, "{:>11}: {}", "cancelled".bold(), notes.cancelled)?;
+        }
+        if notes.unimplemented > 0 {
+            let unimplemented = notes.unimplemented;
+            writeln!(out, "{:>11}: {unimplemented}", "unsupported".bold())?;
+        }
+        if notes.noise > 0 {
+            writeln!(out, "{:>11}: {} lines", "noise".bold(), notes.noise)?;
+        }
+        if let (count @ 1.., bytes) = notes.references {
+            let files = if count == 1 { "file" } else { "files" };
+            writeln!(
+                out,
+                "{:>11}: {count} {files}, {}",
+                "references".bold(),
+                bytestring(bytes),
+            )?;
+        }
+        let (matched, differed) = notes.golden;
+        if matched + differed > 0 {
+            writeln!(
+                out,
+                "{:>11}: {matched} matched, {differed} differed",
+                "golden".bold(),
+            )?;
+        }
         writeln!(out, "{:>11}: {}", "elapsed".bold(), nanostring(elapsed_ns))?;
+        if let Some(usage) = notes.usage {
+            let Usage {
+                max_rss_bytes,
+                user,
+                system,
+                voluntary_context_switches,
+                involuntary_context_switches,
+            } = usage;
+            writeln!(
+                out,
+                "{:>11}: {}",
+                "peak memory".bold(),
+                bytestring(max_rss_bytes)
+            )?;
+            writeln!(
+                out,
+                "{:>11}: {}",
+                "user time".bold(),
+                nanostring(user.nanoseconds)
+            )?;
+            writeln!(
+                out,
+                "{:>11}: {}",
+                "system time".bold(),
+                nanostring(system.nanoseconds)
+            )?;
+            if let (Some(voluntary), Some(involuntary)) =
+                (voluntary_context_switches, involuntary_context_switches)
+            {
+                writeln!(
+                    out,
+                    "{:>11}: {voluntary} voluntary, {involuntary} involuntary",
+                    "switches".bold(),
+                )?;
+            }
+        }
+        if let Some(footer) = &notes.footer {
+            writeln!(out, "{:>11}: {}", "outcome".bold(), footer.outcome)?;
+        }
 
-        if interrupted {
+        if let Some(late) = late {
+            let late = format!("{} The session was already complete.", late.describe());
+            writeln!(out, "{}", late.yellow())?
+        }
+        if let Some(ending) = ending {
+            writeln!(out, "{}", ending.describe().red())?
+        } else if interrupted {
             writeln!(
                 out,
                 "{}",
@@ -150,6 +465,14 @@ impl InOut<anyhow::Result<()>> for Summary {
                     .red()
             )?
         }
+        if notes.footer.is_none() {
+            writeln!(
+                out,
+                "{}",
+                "Log has no footer, so the session did not finish and the log may be incomplete."
+                    .red()
+            )?
+        }
 
         Ok(())
     }
@@ -218,6 +541,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_trim_timeout() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 3528846445 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 13528846445 }, "timeout": { "phase": "evaluate", "nanoseconds": 10000000000 } }
+{ "elapsed": { "nanoseconds": 13530000000 }, "footer": { "outcome": "timeout", "eval": { "status": 0 }, "tool": { "signal": 9 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Trim.run(input_cursor, &mut output)?;
+        write_goldenfile("trim_timeout.jsonl", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn test_trim_noise() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 3528846445 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 3530000000 }, "noise": "Precompiling packages..." }
+{ "elapsed": { "nanoseconds": 3538846445 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 3538846445 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 3540000000 }, "noise": "" }
+{ "elapsed": { "nanoseconds": 3543823169 }, "response": {"id": 2, "success": true, "output": 1.0, "timings": [{"name": "evaluate", "nanoseconds": 0}]} }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Trim.run(input_cursor, &mut output)?;
+        write_goldenfile("trim_noise.jsonl", &output);
+        Ok(())
+    }
+
     #[test]
     fn summary_simple() -> anyhow::Result<()> {
         let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
@@ -262,4 +614,176 @@ mod tests {
         write_goldenfile("summary_noresponse.txt", &output);
         Ok(())
     }
+
+    #[test]
+    fn summary_timeout() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "futhark", "config": {"backend": "c"}} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 61033761149 }, "timeout": { "phase": "define", "nanoseconds": 60000000000 } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_timeout.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_end_timeout() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "pytorch", "features": ["end"]} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "protocol": {"version": 1, "features": ["end"]} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "end"} }
+{ "elapsed": { "nanoseconds": 12129140232 }, "timeout": { "phase": "end", "nanoseconds": 10000000000 } }
+{ "elapsed": { "nanoseconds": 12140000000 }, "footer": { "outcome": "success", "eval": { "status": 0 }, "tool": { "signal": 9 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_end_timeout.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_cancelled() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "futhark", "features": ["cancel"]} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "protocol": {"version": 1, "features": ["cancel"]} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 12129140232 }, "response": {"error": "evaluate timed out, so the tool cancelled it", "id": 2, "success": false} }
+{ "elapsed": { "nanoseconds": 12129140232 }, "cancelled": { "phase": "evaluate", "nanoseconds": 10000000000 } }
+{ "elapsed": { "nanoseconds": 12129533619 }, "message": {"id": 3, "kind": "evaluate", "module": "hello", "function": "square", "input": 2.0} }
+{ "elapsed": { "nanoseconds": 12132741440 }, "response": {"id": 3, "success": true, "output": 4.0, "timings": [{"name": "evaluate", "nanoseconds": 1000}]} }
+{ "elapsed": { "nanoseconds": 12140000000 }, "footer": { "outcome": "failure", "eval": { "status": 0 }, "tool": { "status": 0 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_cancelled.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_unimplemented() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "scilean", "modules": {"hello": ["square"]}} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 2132741440 }, "response": {"id": 2, "success": true, "output": 1.0, "timings": [{"name": "evaluate", "nanoseconds": 1000}]} }
+{ "elapsed": { "nanoseconds": 2133000000 }, "message": {"id": 3, "kind": "evaluate", "module": "hello", "function": "double", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 2133000000 }, "response": {"id": 3, "success": false, "error": "tool does not implement hello::double"} }
+{ "elapsed": { "nanoseconds": 2133000000 }, "unimplemented": 3 }
+{ "elapsed": { "nanoseconds": 2140000000 }, "footer": { "outcome": "undefined", "eval": { "status": 0 }, "tool": { "status": 0 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_unimplemented.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_noise() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033000000 }, "noise": "Precompiling packages..." }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "julia"} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 2130000000 }, "noise": "W tensorflow/core/util/port.cc:113] oneDNN custom operations are on." }
+{ "elapsed": { "nanoseconds": 2131000000 }, "noise": "" }
+{ "elapsed": { "nanoseconds": 2132741440 }, "response": {"id": 2, "success": true, "output": 1.0, "timings": [{"name": "evaluate", "nanoseconds": 1000}]} }
+{ "elapsed": { "nanoseconds": 2140000000 }, "footer": { "outcome": "success", "eval": { "status": 0 }, "tool": { "status": 0 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_noise.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_references() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "ba", "features": ["refs"]} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "futhark", "features": ["refs"]} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "protocol": {"version": 1, "features": ["refs"]} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "ba"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "evaluate", "module": "ba", "function": "jacobian", "input_ref": "input-2.json", "description": "ba13"} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "reference": {"path": "input-2.json", "bytes": 41943040, "sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"} }
+{ "elapsed": { "nanoseconds": 9132741440 }, "response": {"id": 2, "success": true, "output_ref": "output-2.json", "timings": [{"name": "evaluate", "nanoseconds": 6000000000}]} }
+{ "elapsed": { "nanoseconds": 9132741440 }, "reference": {"path": "output-2.json", "bytes": 20971520, "sha256": "60303ae22b998861bce3b28f33eec1be758a213c86c93c076dbe9f558c11c752"} }
+{ "elapsed": { "nanoseconds": 9140000000 }, "footer": { "outcome": "success", "eval": { "status": 0 }, "tool": { "status": 0 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_references.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_golden() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "pytorch"} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2129140232 }, "message": {"id": 2, "kind": "evaluate", "module": "hello", "function": "square", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 2132741440 }, "response": {"id": 2, "success": true, "output": 1.0, "timings": [{"name": "evaluate", "nanoseconds": 3000000}]} }
+{ "elapsed": { "nanoseconds": 2132741440 }, "golden": {"of": 2, "valid": true} }
+{ "elapsed": { "nanoseconds": 2133000000 }, "message": {"id": 3, "kind": "analysis", "of": 2, "valid": true} }
+{ "elapsed": { "nanoseconds": 2133100000 }, "response": {"id": 3} }
+{ "elapsed": { "nanoseconds": 2134140232 }, "message": {"id": 4, "kind": "evaluate", "module": "hello", "function": "double", "input": 1.0} }
+{ "elapsed": { "nanoseconds": 2135741440 }, "response": {"id": 4, "success": true, "output": 3.0, "timings": [{"name": "evaluate", "nanoseconds": 1000000}]} }
+{ "elapsed": { "nanoseconds": 2135741440 }, "golden": {"of": 4, "valid": false, "error": "output differs: expected 2.0, got 3.0"} }
+{ "elapsed": { "nanoseconds": 2136000000 }, "message": {"id": 5, "kind": "analysis", "of": 4, "valid": true} }
+{ "elapsed": { "nanoseconds": 2136100000 }, "response": {"id": 5} }
+{ "elapsed": { "nanoseconds": 2140000000 }, "footer": { "outcome": "invalid", "eval": { "status": 0 }, "tool": { "status": 0 } } }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_golden.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_usage() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 0 }, "host": {"hostname": "bench1", "cpu": "AMD EPYC 7763", "cores": 4, "kernel": "Linux 6.8.0", "memory_bytes": 17179869184, "gradbench": "0.0.0", "commit": "0123456789abcdef0123456789abcdef01234567", "images": {"ghcr.io/gradbench/tool-futhark:latest": "sha256:0123456789abcdef"}} }
+{ "elapsed": { "nanoseconds": 0 }, "settings": {"cpus": "2-3", "env": {"OMP_NUM_THREADS": "1"}} }
+{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "futhark", "config": {"backend": "c"}, "protocol_version": 1, "features": ["foo"]} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "protocol": {"version": 1, "features": ["foo"]} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 2128460174 }, "response": {"id": 1, "success": true} }
+{ "elapsed": { "nanoseconds": 2130000000 }, "usage": {"max_rss_bytes": 52428800, "user": {"nanoseconds": 1500000000}, "system": {"nanoseconds": 250000000}, "voluntary_context_switches": 42, "involuntary_context_switches": 7} }
+{ "elapsed": { "nanoseconds": 2130000001 }, "footer": {"outcome": "success", "eval": {"status": 0}, "tool": {"status": 0}} }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_usage.txt", &output);
+        Ok(())
+    }
+
+    #[test]
+    fn summary_crash() -> anyhow::Result<()> {
+        let input = r#"{ "elapsed": { "nanoseconds": 1032662218 }, "message": {"id": 0, "kind": "start", "eval": "hello"} }
+{ "elapsed": { "nanoseconds": 1033286058 }, "response": {"id": 0, "tool": "futhark", "config": {"backend": "c"}} }
+{ "elapsed": { "nanoseconds": 1033761149 }, "message": {"id": 1, "kind": "define", "module": "hello"} }
+{ "elapsed": { "nanoseconds": 1533761149 }, "crash": {"signal": 11} }
+{ "elapsed": { "nanoseconds": 1533761150 }, "footer": {"outcome": "crash", "eval": {"status": 1}, "tool": {"signal": 11}} }
+"#;
+        let input_cursor = Cursor::new(input.as_bytes());
+        let mut output: Vec<u8> = Vec::new();
+        log::Summary.run(&mut std::io::BufReader::new(input_cursor), &mut output)?;
+        write_goldenfile("summary_crash.txt", &output);
+        Ok(())
+    }
 }
@@ -1,7 +1,14 @@
+mod arrays;
+mod events;
+mod golden;
+mod host;
 mod intermediary;
 mod lint;
 mod log;
+mod mock;
 mod protocol;
+mod replay;
+mod schema;
 mod stats;
 mod util;
 
@@ -11,12 +18,18 @@ use std::{
     env,
     fmt::Write as _,
     fs,
-    io::{self, BufRead},
+    io::{self, BufRead, Write as _},
     mem::take,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
     process::{Command, ExitCode, ExitStatus, Output, Stdio},
     rc::Rc,
     str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
     time::Duration,
 };
 
@@ -24,11 +37,15 @@ use anyhow::{anyhow, bail, Context};
 use clap::{Parser, Subcommand};
 use colored::{Color, Colorize};
 use itertools::Itertools;
+use protocol::Settings;
 use regex::Regex;
 use serde::Serialize;
 use stats::StatsMetadata;
 use strum::{EnumIter, EnumString, IntoStaticStr};
-use util::{run_in_out, shlex_cmd, CtrlC};
+use util::{
+    constrain, container_id_file, copy_command, lock, open_log, partition_cpus, run_in_out, share,
+    shlex_cmd, tool_settings, Console, CtrlC, InOut, Tolerance,
+};
 
 /// CLI utilities for GradBench, a benchmark suite for differentiable programming across languages
 /// and domains.
@@ -43,7 +60,7 @@ struct Cli {
 
 /// Help text for the `outcome` argument of the `exit-code` subcommand.
 const OUTCOME_HELP: &str =
-    "One of `interrupt`, `timeout`, `invalid`, `failure`, `undefined`, `error`, or `success`";
+    "One of `malformed`, `crash`, `violation`, `interrupt`, `timeout`, `invalid`, `failure`, `undefined`, `error`, or `success`";
 
 #[derive(Debug, Subcommand)]
 enum Commands {
@@ -99,16 +116,134 @@ enum Commands {
         eval: String,
 
         /// A shell script to run the tool. For example: `gradbench tool pytorch`
-        #[clap(long)]
-        tool: String,
+        ///
+        /// If given more than once, every message from the eval is sent to every tool, but only
+        /// the responses of the first tool are sent back to the eval. The outputs of the other
+        /// tools are compared to those of the first, and any differences are reported and make the
+        /// outcome `invalid`. No optional protocol features are offered to the tools in that case,
+        /// since each of them would have to agree to the same ones.
+        #[clap(long, required = true)]
+        tool: Vec<String>,
 
         /// A path to save the full log. For example: `log.jsonl`
+        ///
+        /// The stderr of the eval and tool are also captured and saved next to the log, with each
+        /// line stamped by elapsed time. For example: `log.eval.stderr` and `log.tool.stderr`
+        ///
+        /// Each other tool gets its own log and stderr file. For example: `log.1.jsonl` and
+        /// `log.1.tool.stderr` for the second tool
+        ///
+        /// If the path ends with `.gz` or `.zst`, the logs are compressed with gzip or Zstandard.
+        /// For example: `log.jsonl.zst`
+        ///
+        /// Each log is written with `.partial` appended to its name, and only renamed once the
+        /// session is over and a footer with the outcome has been written. For example:
+        /// `log.jsonl.partial`
         #[clap(short, long)]
         output: Option<PathBuf>,
 
-        /// The timeout, in seconds, for tool responses (not implemented on Windows)
+        /// The timeout, in seconds, for tool responses, which the eval can shorten for a define or
+        /// evaluate message but never lengthen (not implemented on Windows)
         #[clap(long)]
         timeout: Option<u64>,
+
+        /// The timeout, in seconds, for the tool to respond to the start message
+        #[clap(long, value_name = "TIMEOUT")]
+        start_timeout: Option<u64>,
+
+        /// The timeout, in seconds, for the tool to respond to each define message
+        #[clap(long, value_name = "TIMEOUT")]
+        define_timeout: Option<u64>,
+
+        /// The timeout, in seconds, for the tool to respond to each evaluate message
+        #[clap(long, value_name = "TIMEOUT")]
+        evaluate_timeout: Option<u64>,
+
+        /// The total time, in seconds, for the tool to respond to every message in the session
+        #[clap(long, value_name = "TIMEOUT")]
+        session_timeout: Option<u64>,
+
+        /// The time, in seconds, for the tool to respond to the end message if it supports one, and
+        /// then to exit before it is killed, or to respond to a cancel message after a timed out
+        /// evaluation if it supports those [default: 10] (not implemented on Windows)
+        #[clap(long, value_name = "SECONDS")]
+        grace: Option<u64>,
+
+        /// Pin tools to a list of CPUs like `0-3,6`, using `--cpuset-cpus` for tools run with
+        /// `docker run` (only implemented on Linux otherwise)
+        #[clap(long, value_name = "LIST")]
+        cpus: Option<String>,
+
+        /// Limit the number of threads used by the numerical libraries in tools, by setting
+        /// `OMP_NUM_THREADS`, `MKL_NUM_THREADS`, `OPENBLAS_NUM_THREADS`, `JULIA_NUM_THREADS`,
+        /// `TF_NUM_INTRAOP_THREADS`, and `TF_NUM_INTEROP_THREADS`, and for a single thread also
+        /// adding `--xla_cpu_multi_thread_eigen=false` to `XLA_FLAGS`
+        #[clap(long, value_name = "N")]
+        threads: Option<NonZeroUsize>,
+
+        /// Check every rule of the protocol, failing on any violation by the eval or tool
+        #[clap(long)]
+        strict: bool,
+
+        /// Also print captured stderr from the eval and tool to the console, which requires `-o`
+        #[clap(long, requires = "output")]
+        echo_stderr: bool,
+
+        /// Skip over lines that tools print to stdout which aren't JSON objects with an `id`,
+        /// logging them as noise
+        #[clap(long)]
+        lenient_stdout: bool,
+
+        /// A directory to share with the eval and tools, so they can pass large inputs and outputs
+        /// as files if they agree on the `refs` feature, by setting `GRADBENCH_SCRATCH` and
+        /// mounting it for `docker run`
+        #[clap(long, value_name = "DIR")]
+        scratch: Option<PathBuf>,
+
+        /// A directory of JSON Schemas for the eval, with a `<module>.json` file mapping each
+        /// function to the schemas of its input and output, to check every evaluation against
+        #[clap(long, value_name = "DIR")]
+        validate_schema: Option<PathBuf>,
+
+        /// The untrimmed log of a known-good session, to check that each output matches the one
+        /// for the same function and description, regardless of what the eval says about it; this
+        /// can't be a session whose tool passed any outputs by reference, since those aren't in
+        /// the log
+        #[clap(long, value_name = "PATH")]
+        reference: Option<PathBuf>,
+
+        /// The tolerance for comparing one field of the outputs to the reference, like
+        /// `gradient=1e-6,1e-9` for an absolute and a relative tolerance; the rest use `--atol` and
+        /// `--rtol`
+        #[clap(long, value_name = "FIELD=ATOL[,RTOL]")]
+        reference_tolerance: Vec<golden::FieldTolerance>,
+
+        /// The absolute tolerance for comparing numbers output by several tools, or to the reference
+        #[clap(long, value_name = "TOLERANCE", default_value_t = 1e-8)]
+        atol: f64,
+
+        /// The tolerance for comparing numbers output by several tools, or to the reference,
+        /// relative to the magnitude of the number output by the first tool or the reference
+        #[clap(long, value_name = "TOLERANCE", default_value_t = 1e-5)]
+        rtol: f64,
+
+        /// Print a machine-readable stream of events instead of the usual output
+        ///
+        /// The only format is `json`, which prints one line of JSON per event. Each event has a
+        /// `"version"` of the format, the `"elapsed"` time, and an `"event"` saying what happened:
+        /// a `"message"` when each message is sent to the tool, a `"round"` once it responds, or a
+        /// `"timeout"`, `"cancel"`, `"mismatch"`, `"crash"`, `"error"`, or `"end"`
+        #[clap(long, value_name = "FORMAT", value_parser = ["json"])]
+        events: Option<String>,
+
+        /// Write the events to a file instead, keeping the usual output. For example: `events.jsonl`
+        #[clap(long, value_name = "PATH", requires = "events")]
+        events_output: Option<PathBuf>,
+
+        /// A log from an earlier run of the same eval and tool, to show progress and estimate the
+        /// time remaining
+        #[clap(long, value_name = "PATH")]
+        progress_from: Option<PathBuf>,
     },
 
     /// Return a `gradbench run` exit code corresponding to a specific outcome.
@@ -118,6 +253,9 @@ enum Commands {
     },
 
     /// Perform useful operations on the log files produced by `gradbench run`.
+    ///
+    /// Log files compressed with gzip or Zstandard are read transparently, detecting the codec from
+    /// a `.gz` or `.zst` extension or else from the first bytes of the file.
     Log {
         #[command(subcommand)]
         command: LogCommands,
@@ -133,6 +271,8 @@ enum Commands {
     },
 }
 
+// This is only parsed once from the command line, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Subcommand)]
 enum RepoCommands {
     /// Build and run one or more evals against one of more tools, using Docker.
@@ -148,7 +288,8 @@ enum RepoCommands {
     ///
     /// The output directory will contain a `<EVAL>/<TOOL>.jsonl` file for each `<EVAL>` and each
     /// `<TOOL>`, where the eval and tool names are mangled to only contain ASCII letters, digits,
-    /// and hyphens. It is an error for two mangled names to conflict.
+    /// and hyphens. It is an error for two mangled names to conflict. Next to each log file, the
+    /// stderr of the eval and tool are saved to `<TOOL>.eval.stderr` and `<TOOL>.tool.stderr`.
     Run {
         /// One or more evals to run, or all evals by default
         #[clap(short, long)]
@@ -170,14 +311,83 @@ enum RepoCommands {
         #[clap(short, long)]
         output: Option<PathBuf>,
 
-        /// The timeout, in seconds, for tool responses (not implemented on Windows)
+        /// The timeout, in seconds, for tool responses, which the eval can shorten for a define or
+        /// evaluate message but never lengthen (not implemented on Windows)
         #[clap(long)]
         timeout: Option<u64>,
 
+        /// The timeout, in seconds, for the tool to respond to the start message
+        #[clap(long, value_name = "TIMEOUT")]
+        start_timeout: Option<u64>,
+
+        /// The timeout, in seconds, for the tool to respond to each define message
+        #[clap(long, value_name = "TIMEOUT")]
+        define_timeout: Option<u64>,
+
+        /// The timeout, in seconds, for the tool to respond to each evaluate message
+        #[clap(long, value_name = "TIMEOUT")]
+        evaluate_timeout: Option<u64>,
+
+        /// The total time, in seconds, for the tool to respond to every message in the session
+        #[clap(long, value_name = "TIMEOUT")]
+        session_timeout: Option<u64>,
+
+        /// The time, in seconds, for the tool to respond to the end message if it supports one, and
+        /// then to exit before it is killed, or to respond to a cancel message after a timed out
+        /// evaluation if it supports those [default: 10] (not implemented on Windows)
+        #[clap(long, value_name = "SECONDS")]
+        grace: Option<u64>,
+
+        /// Pin tools to a list of CPUs like `0-3,6`, using `--cpuset-cpus` for tools run with
+        /// `docker run` (only implemented on Linux otherwise)
+        #[clap(long, value_name = "LIST")]
+        cpus: Option<String>,
+
+        /// Limit the number of threads used by the numerical libraries in tools, by setting
+        /// `OMP_NUM_THREADS`, `MKL_NUM_THREADS`, `OPENBLAS_NUM_THREADS`, `JULIA_NUM_THREADS`,
+        /// `TF_NUM_INTRAOP_THREADS`, and `TF_NUM_INTEROP_THREADS`, and for a single thread also
+        /// adding `--xla_cpu_multi_thread_eigen=false` to `XLA_FLAGS`
+        #[clap(long, value_name = "N")]
+        threads: Option<NonZeroUsize>,
+
+        /// Check every rule of the protocol, failing on any violation by an eval or tool
+        #[clap(long)]
+        strict: bool,
+
+        /// Also print captured stderr from evals and tools to the console, which requires `-o`
+        #[clap(long, requires = "output")]
+        echo_stderr: bool,
+
+        /// Skip over lines that tools print to stdout which aren't JSON objects with an `id`,
+        /// logging them as noise
+        #[clap(long)]
+        lenient_stdout: bool,
+
+        /// A directory to share with evals and tools, so they can pass large inputs and outputs as
+        /// files if they agree on the `refs` feature, by setting `GRADBENCH_SCRATCH` and mounting
+        /// it for `docker run`; each pair of eval and tool gets its own subdirectory, like the logs
+        #[clap(long, value_name = "DIR")]
+        scratch: Option<PathBuf>,
+
         /// Only allow known named evals and tools, and check against their expected outcome
         #[clap(long)]
         check: bool,
 
+        /// Also write a machine-readable stream of events for each eval and tool next to its log,
+        /// with `.events.jsonl` in place of `.jsonl`, as printed by `gradbench run --events`
+        #[clap(long, value_name = "FORMAT", value_parser = ["json"], requires = "output")]
+        events: Option<String>,
+
+        /// Run up to N eval/tool pairs at once, printing the output of each as a block once it
+        /// finishes
+        #[clap(long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+
+        /// Split the `--cpus` list into a disjoint group for each of the `--jobs`, so that
+        /// concurrent tools don't compete for CPUs and disturb each other's timings
+        #[clap(long, requires = "cpus")]
+        partition_cpus: bool,
+
         /// Download evals and tools from GitHub Actions instead of building locally
         #[clap(long, value_name = "RUN_ID")]
         download_github: Option<u64>,
@@ -322,7 +532,10 @@ enum RepoCommands {
     /// Generate summary data files and plots from a directory containing log files.
     ///
     /// The directory should contain a `<EVAL>/<TOOL>.jsonl` file for each `<EVAL>` under `evals`
-    /// and each `<TOOL>` under `tools`.
+    /// and each `<TOOL>` under `tools`. Each file may instead be compressed as `<TOOL>.jsonl.gz` or
+    /// `<TOOL>.jsonl.zst`. A log with a host but no footer is refused, since the session that wrote
+    /// it did not finish; older logs, which have neither, only get a warning. The hosts recorded at
+    /// the start of the logs are listed in the summary, with a warning if there is more than one.
     Stats {
         /// The directory containing log files
         input: PathBuf,
@@ -348,12 +561,12 @@ enum LogCommands {
     /// Writes to stdout unless the `--output` option is used. It is expected that the input log
     /// file is well-formed, but not that it corresponds to a successful run. In particular, the
     /// final message may not have a response - this occurs when the tool crashes or times out
-    /// before it gets to respond.
+    /// before it gets to respond, and in the latter case the log ends with a timeout entry.
     Trim {
         /// The input log file
         input: Option<PathBuf>,
 
-        /// The output log file
+        /// The output log file, which is compressed if it ends with `.gz` or `.zst`
         #[clap(short, long)]
         output: Option<PathBuf>,
     },
@@ -376,6 +589,51 @@ enum LogCommands {
         #[clap(short, long)]
         output: PathBuf,
     },
+
+    /// Drive a tool by replaying the messages from a log file, without running the eval.
+    ///
+    /// The log file must not have been trimmed, nor be from a session with the "refs" feature,
+    /// whose files the eval has since deleted. Instead of forwarding the analysis messages that
+    /// the eval originally sent, each output from the tool is compared to the output recorded in
+    /// the log, allowing numbers to differ by the given tolerance. The exit code is the same as
+    /// `run`.
+    Replay {
+        /// The input log file
+        input: PathBuf,
+
+        /// A shell script to run the tool. For example: `gradbench tool pytorch`
+        #[clap(long)]
+        tool: String,
+
+        /// A path to save the full log of the replay. For example: `log.jsonl`
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+
+        /// The timeout, in seconds, for tool responses (not implemented on Windows)
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// The tolerance for numbers in outputs, both absolute and relative to the magnitude of the
+        /// recorded number
+        #[clap(long, default_value_t = 1e-6)]
+        tolerance: f64,
+    },
+
+    /// Act as a tool by answering messages on stdin with the responses recorded in a log file.
+    ///
+    /// The log file must not have been trimmed, nor be from a session with the "refs" feature,
+    /// whose files the eval has since deleted. Each message is answered with the recorded response
+    /// to a message of the same kind for the same module, function, and input; if there is none,
+    /// the response reports failure. For example, to test an eval:
+    /// `gradbench run --eval "gradbench eval hello" --tool "gradbench log mock-tool log.jsonl"`
+    MockTool {
+        /// The input log file
+        input: PathBuf,
+
+        /// Wait as long as the recorded tool took before sending each response
+        #[clap(long)]
+        delays: bool,
+    },
 }
 
 /// Print `error` to stderr, then return [`ExitCode::FAILURE`].
@@ -628,6 +886,19 @@ fn docker_build_quiet(color: Color, mut cmd: Command) -> anyhow::Result<(Caching
 #[serde(rename_all = "kebab-case")]
 #[strum(serialize_all = "kebab-case")]
 enum BadOutcome {
+    /// The tool gave some number of outputs that don't match the eval's schema.
+    ///
+    /// This takes precedence over `invalid`, `failure` and `undefined` in a session that has more
+    /// than one of them, but outcomes that end the session early, such as `crash` and `timeout`,
+    /// are reported as such. An input from the eval that doesn't match is an `error` instead.
+    Malformed,
+
+    /// The tool exited in the middle of the session.
+    Crash,
+
+    /// The eval or tool violated the protocol, as checked in strict mode.
+    Violation,
+
     /// The user sent an interrupt signal.
     Interrupt,
 
@@ -650,6 +921,9 @@ enum BadOutcome {
 impl From<BadOutcome> for ExitCode {
     fn from(outcome: BadOutcome) -> Self {
         match outcome {
+            BadOutcome::Malformed => ExitCode::from(9),
+            BadOutcome::Crash => ExitCode::from(8),
+            BadOutcome::Violation => ExitCode::from(7),
             BadOutcome::Interrupt => ExitCode::from(6),
             BadOutcome::Timeout => ExitCode::from(5),
             BadOutcome::Invalid => ExitCode::from(4),
@@ -692,6 +966,14 @@ fn configure_intermediary_subcommand(cmd: &mut Command) {
     std::os::unix::process::CommandExt::process_group(cmd, 0);
 }
 
+/// Create the scratch directory at `path` if needed, returning its absolute path so that it can
+/// be mounted at the same place in containers.
+fn scratch_dir(path: &Path) -> anyhow::Result<PathBuf> {
+    fs::create_dir_all(path)
+        .and_then(|()| fs::canonicalize(path))
+        .with_context(|| format!("{}", path.display()))
+}
+
 /// List the entries in a directory.
 fn ls(dir: &str) -> anyhow::Result<Vec<String>> {
     fs::read_dir(dir)
@@ -714,11 +996,53 @@ struct RunConfig {
     /// The timeout, in seconds, for tool responses (not implemented on Windows).
     timeout: Option<u64>,
 
+    /// The timeout, in seconds, for the tool to respond to the start message.
+    start_timeout: Option<u64>,
+
+    /// The timeout, in seconds, for the tool to respond to each define message.
+    define_timeout: Option<u64>,
+
+    /// The timeout, in seconds, for the tool to respond to each evaluate message.
+    evaluate_timeout: Option<u64>,
+
+    /// The total time, in seconds, for the tool to respond to every message in the session.
+    session_timeout: Option<u64>,
+
+    /// The time, in seconds, for the tool to finish up once the session is over.
+    grace: Option<u64>,
+
+    /// The CPUs to pin tools to.
+    cpus: Option<String>,
+
+    /// The number of threads for numerical libraries in tools.
+    threads: Option<NonZeroUsize>,
+
+    /// Check every rule of the protocol.
+    strict: bool,
+
+    /// Also print captured stderr from evals and tools to the console.
+    echo_stderr: bool,
+
+    /// Skip over lines of tool output that aren't JSON.
+    lenient_stdout: bool,
+
+    /// A directory to share with evals and tools for passing inputs and outputs by reference.
+    scratch: Option<PathBuf>,
+
     /// Only allow known named evals and tools, and check against their expected outcome.
     check: bool,
 
     /// GitHub Actions run ID from which to download evals and tools.
     download_github: Option<u64>,
+
+    /// Write events next to each log.
+    events: bool,
+
+    /// The number of eval/tool pairs to run at once.
+    jobs: Option<NonZeroUsize>,
+
+    /// Give each job its own disjoint group of the CPUs.
+    partition_cpus: bool,
 }
 
 /// Raw lists of evals and tools to run against each other.
@@ -778,13 +1102,23 @@ fn eval_subpath(dir: &Path, eval: &str) -> PathBuf {
     dir.join(mangle(eval))
 }
 
+/// Given a directory and raw eval/tool command strings, return a path for that pair.
+fn pair_subpath(dir: &Path, eval: &str, tool: &str) -> PathBuf {
+    eval_subpath(dir, eval).join(mangle(tool))
+}
+
 /// Given a log directory and raw eval/tool command strings, return a path for that log.
 fn log_subpath(dir: &Path, eval: &str, tool: &str) -> PathBuf {
-    let mut path = eval_subpath(dir, eval).join(mangle(tool));
+    let mut path = pair_subpath(dir, eval, tool);
     path.set_extension("jsonl");
     path
 }
 
+/// Given the path of a log, return the path of the events written alongside it.
+fn events_path(log: &Path) -> PathBuf {
+    log.with_extension("events.jsonl")
+}
+
 /// Process a human-friendly list of evals or tools into a deduplicated build list and a run list.
 fn process_run_items(
     item_kind: RunItemKind,
@@ -916,6 +1250,15 @@ fn run_dry(
         }
         writeln!(stdout)?;
     }
+    // The commands are printed in order, so show each one with the CPUs it would get if the jobs
+    // took turns.
+    let groups = match &cfg.cpus {
+        Some(cpus) if cfg.partition_cpus => {
+            partition_cpus(cpus, cfg.jobs.unwrap_or(NonZeroUsize::MIN))?
+        }
+        _ => cfg.cpus.iter().cloned().collect(),
+    };
+    let mut groups = groups.iter().cycle();
     for (eval_string, eval_cmd) in evals_run {
         for (tool_string, tool_cmd) in tools_run {
             let eval = shlex_cmd(eval_cmd)?;
@@ -924,6 +1267,43 @@ fn run_dry(
             if let Some(seconds) = cfg.timeout {
                 write!(stdout, " --timeout {seconds}")?;
             }
+            if let Some(seconds) = cfg.start_timeout {
+                write!(stdout, " --start-timeout {seconds}")?;
+            }
+            if let Some(seconds) = cfg.define_timeout {
+                write!(stdout, " --define-timeout {seconds}")?;
+            }
+            if let Some(seconds) = cfg.evaluate_timeout {
+                write!(stdout, " --evaluate-timeout {seconds}")?;
+            }
+            if let Some(seconds) = cfg.session_timeout {
+                write!(stdout, " --session-timeout {seconds}")?;
+            }
+            if let Some(seconds) = cfg.grace {
+                write!(stdout, " --grace {seconds}")?;
+            }
+            if let Some(cpus) = groups.next() {
+                write!(stdout, " --cpus {}", shlex::try_quote(cpus)?)?;
+            }
+            if let Some(threads) = cfg.threads {
+                write!(stdout, " --threads {threads}")?;
+            }
+            if cfg.strict {
+                write!(stdout, " --strict")?;
+            }
+            if cfg.echo_stderr {
+                write!(stdout, " --echo-stderr")?;
+            }
+            if cfg.lenient_stdout {
+                write!(stdout, " --lenient-stdout")?;
+            }
+            if let Some(dir) = &cfg.scratch {
+                let dir = pair_subpath(dir, eval_string, tool_string);
+                let dir_str = dir.to_str().ok_or_else(|| {
+                    anyhow!("failed to convert scratch directory path to a string: {dir:?}")
+                })?;
+                write!(stdout, " --scratch {}", shlex::try_quote(dir_str)?)?;
+            }
             write!(stdout, " --eval {}", shlex::try_quote(&eval)?)?;
             write!(stdout, " --tool {}", shlex::try_quote(&tool)?)?;
             if let Some(dir) = &cfg.output {
@@ -932,6 +1312,14 @@ fn run_dry(
                     anyhow!("failed to convert output file path to a string: {path:?}")
                 })?;
                 write!(stdout, " -o {}", shlex::try_quote(path_str)?)?;
+                if cfg.events {
+                    let path = events_path(&path);
+                    let path_str = path.to_str().ok_or_else(|| {
+                        anyhow!("failed to convert events file path to a string: {path:?}")
+                    })?;
+                    write!(stdout, " --events json --events-output")?;
+                    write!(stdout, " {}", shlex::try_quote(path_str)?)?;
+                }
             }
             writeln!(stdout)?;
         }
@@ -939,9 +1327,15 @@ fn run_dry(
     Ok(())
 }
 
+/// Read an earlier log to estimate the progress of a new session, if the log exists and is valid.
+fn read_progress(path: &Path) -> Option<intermediary::Progress> {
+    let mut log = open_log(path).ok()?;
+    intermediary::Progress::read(&mut log).ok()
+}
+
 /// Build and run one or more evals against one or more tools.
 fn run_multiple(
-    ctrl_c: &mut CtrlC,
+    ctrl_c: &CtrlC,
     cfg: RunConfig,
     RunRaw {
         eval,
@@ -952,9 +1346,9 @@ fn run_multiple(
     }: RunRaw,
 ) -> anyhow::Result<Result<(), ExitCode>> {
     let evals = ls("evals")?;
-    let (evals_build, mut evals_run) =
+    let (evals_build, evals_run) =
         process_run_items(RunItemKind::Eval, eval, no_eval, || Ok(evals.clone()))?;
-    let (tools_build, mut tools_run) =
+    let (tools_build, tools_run) =
         process_run_items(RunItemKind::Tool, tool, no_tool, || ls("tools"))?;
     if dry_run {
         let this = env::args()
@@ -1056,80 +1450,265 @@ fn run_multiple(
             fs::create_dir_all(eval_subpath(dir, eval_string))?;
         }
     }
-    let mut pass = true;
-    let mut first = true;
-    for (eval_string, eval_cmd) in &mut evals_run {
+    let jobs = cfg.jobs.unwrap_or(NonZeroUsize::MIN);
+    // Each job slot gets its own settings, so that partitioned CPUs aren't shared between sessions.
+    let slots: Vec<Option<Settings>> = match &cfg.cpus {
+        Some(cpus) if cfg.partition_cpus => partition_cpus(cpus, jobs)?
+            .into_iter()
+            .map(|group| tool_settings(Some(group), cfg.threads))
+            .collect(),
+        _ => vec![tool_settings(cfg.cpus.clone(), cfg.threads); jobs.get()],
+    };
+    let options = intermediary::Options {
+        timeout: cfg.timeout.map(Duration::from_secs),
+        start_timeout: cfg.start_timeout.map(Duration::from_secs),
+        define_timeout: cfg.define_timeout.map(Duration::from_secs),
+        evaluate_timeout: cfg.evaluate_timeout.map(Duration::from_secs),
+        session_timeout: cfg.session_timeout.map(Duration::from_secs),
+        grace: cfg.grace.map(Duration::from_secs),
+        strict: cfg.strict,
+        echo_stderr: cfg.echo_stderr,
+        lenient_stdout: cfg.lenient_stdout,
+        scratch: cfg.scratch.as_deref().map(scratch_dir).transpose()?,
+        ..Default::default()
+    };
+    // Look up the expected outcomes up front, since the matrix can't be shared between threads.
+    let mut pairs = Vec::new();
+    for (eval_string, eval_cmd) in &evals_run {
         let empty = BTreeMap::new();
         let eval_map = map.get(eval_string.as_str()).unwrap_or(&empty);
-        for (tool_string, tool_cmd) in &mut tools_run {
-            if !first {
-                println!();
-            }
-            first = false;
-            println!(
-                "{} {} {eval_string}",
-                "running".bold(),
-                "eval".blue().bold(),
-            );
-            println!(
-                "{} {} {tool_string}",
-                "   with".bold(),
-                "tool".magenta().bold(),
-            );
-            let log_file = cfg
-                .output
-                .as_ref()
-                .map(|dir| fs::File::create(log_subpath(dir, eval_string, tool_string)))
-                .transpose()?;
-            let outcome = match (eval_cmd.spawn(), tool_cmd.spawn()) {
-                (Ok(mut eval_child), Ok(mut tool_child)) => {
-                    let result = intermediary::run(
-                        ctrl_c,
-                        log_file,
-                        &mut eval_child,
-                        &mut tool_child,
-                        cfg.timeout.map(Duration::from_secs),
-                    );
-                    let _ = eval_child.wait();
-                    let _ = tool_child.wait();
-                    result
-                }
-                _ => Err(BadOutcome::Error),
-            };
-            print!("{} ", " outcome".bold());
-            let actual = match outcome {
-                Ok(()) => "success",
-                Err(BadOutcome::Interrupt) => {
-                    println!("interrupt");
-                    // This process is about to exit, so don't try to start the next one.
-                    return Ok(Ok(()));
-                }
-                Err(bad_outcome) => <&str>::from(bad_outcome),
-            };
-            println!("{actual}");
-            if cfg.check {
-                let expected = eval_map.get(tool_string.as_str()).map(|o| match o {
-                    Some(bad_outcome) => <&str>::from(bad_outcome),
-                    None => "success",
-                });
-                match expected {
-                    Some(o) => {
-                        if actual == o {
-                            println!("{} {}", "expected".green().bold(), o.green());
-                        } else {
-                            println!("{} {}", "expected".red().bold(), o.red());
-                            pass = false;
+        for (tool_string, tool_cmd) in &tools_run {
+            let expected = eval_map.get(tool_string.as_str()).copied();
+            pairs.push(Pair {
+                eval_string,
+                eval_cmd,
+                tool_string,
+                tool_cmd,
+                expected,
+            });
+        }
+    }
+    let next = AtomicUsize::new(0);
+    let interrupted = AtomicBool::new(false);
+    let pass = AtomicBool::new(true);
+    let first = AtomicBool::new(true);
+    let error = Mutex::new(None);
+    thread::scope(|scope| {
+        for settings in &slots {
+            let (pairs, options, cfg) = (&pairs, &options, &cfg);
+            let (next, interrupted, pass, first, error) =
+                (&next, &interrupted, &pass, &first, &error);
+            scope.spawn(move || {
+                while !interrupted.load(Ordering::SeqCst) {
+                    let Some(pair) = pairs.get(next.fetch_add(1, Ordering::SeqCst)) else {
+                        break;
+                    };
+                    // With only one job, print as the session goes rather than all at the end.
+                    let buffer = Arc::new(Mutex::new(Vec::new()));
+                    let console = if jobs.get() == 1 {
+                        if !first.swap(false, Ordering::SeqCst) {
+                            println!();
+                        }
+                        Console::stdout()
+                    } else {
+                        Console::new(buffer.clone())
+                    };
+                    let result = run_pair(ctrl_c, console, cfg, options, settings.as_ref(), pair);
+                    if jobs.get() > 1 {
+                        let mut stdout = io::stdout().lock();
+                        if !first.swap(false, Ordering::SeqCst) {
+                            let _ = writeln!(stdout);
                         }
+                        let _ = stdout.write_all(&lock(&buffer));
+                        let _ = stdout.flush();
                     }
-                    None => {
-                        println!("{} {}", "expected".yellow().bold(), "unknown".yellow());
-                        pass = false;
+                    match result {
+                        Ok(PairResult::Pass) => {}
+                        Ok(PairResult::Fail) => pass.store(false, Ordering::SeqCst),
+                        // This process is about to exit, so don't try to start any more.
+                        Ok(PairResult::Interrupt) => interrupted.store(true, Ordering::SeqCst),
+                        Err(err) => {
+                            interrupted.store(true, Ordering::SeqCst);
+                            lock(error).get_or_insert(err);
+                        }
                     }
-                };
-            }
+                }
+            });
+        }
+    });
+    if let Some(err) = lock(&error).take() {
+        return Err(err);
+    }
+    Ok(
+        if interrupted.load(Ordering::SeqCst) || pass.load(Ordering::SeqCst) {
+            Ok(())
+        } else {
+            Err(ExitCode::FAILURE)
+        },
+    )
+}
+
+/// An eval and tool to run against each other, from [`run_multiple`].
+struct Pair<'a> {
+    /// The raw eval string.
+    eval_string: &'a str,
+
+    /// The eval command, to be copied for the session.
+    eval_cmd: &'a Command,
+
+    /// The raw tool string.
+    tool_string: &'a str,
+
+    /// The tool command, to be copied for the session.
+    tool_cmd: &'a Command,
+
+    /// The expected outcome, if the pair is known.
+    expected: Option<Option<BadOutcome>>,
+}
+
+/// How running a [`Pair`] went.
+enum PairResult {
+    /// The session finished as expected, or there was no expectation to check.
+    Pass,
+
+    /// The session didn't finish as expected.
+    Fail,
+
+    /// The session was interrupted.
+    Interrupt,
+}
+
+/// Run one eval against one tool, printing the progress and outcome to `out`.
+///
+/// A pair that `evals.txt` marks as unsupported isn't run at all, and writes no log.
+fn run_pair(
+    ctrl_c: &CtrlC,
+    mut out: Console,
+    cfg: &RunConfig,
+    options: &intermediary::Options,
+    settings: Option<&Settings>,
+    Pair {
+        eval_string,
+        eval_cmd,
+        tool_string,
+        tool_cmd,
+        expected,
+    }: &Pair,
+) -> anyhow::Result<PairResult> {
+    writeln!(
+        out,
+        "{} {} {eval_string}",
+        "running".bold(),
+        "eval".blue().bold(),
+    )?;
+    writeln!(
+        out,
+        "{} {} {tool_string}",
+        "   with".bold(),
+        "tool".magenta().bold(),
+    )?;
+    // Don't spend a tool start on a session that evals.txt says can only end with `undefined`.
+    if *expected == Some(Some(BadOutcome::Undefined)) {
+        let outcome = <&str>::from(BadOutcome::Undefined);
+        writeln!(
+            out,
+            "{} {outcome} {}",
+            " outcome".bold(),
+            "(skipped)".dimmed()
+        )?;
+        if cfg.check {
+            writeln!(out, "{} {}", "expected".green().bold(), outcome.green())?;
+        }
+        return Ok(PairResult::Pass);
+    }
+    // The commands are copied because each session needs its own settings and stdio.
+    let mut eval_cmd = copy_command(eval_cmd);
+    let mut tool_cmd = copy_command(tool_cmd);
+    if let Some(settings) = settings {
+        constrain(&mut tool_cmd, settings)?;
+    }
+    // Concurrent sessions would trip over each other's files, so each pair gets its own directory.
+    let scratch = (options.scratch.as_deref())
+        .map(|dir| scratch_dir(&pair_subpath(dir, eval_string, tool_string)))
+        .transpose()?;
+    if let Some(dir) = &scratch {
+        share(&mut eval_cmd, dir);
+        share(&mut tool_cmd, dir);
+    }
+    let container = container_id_file(&mut tool_cmd)?;
+    configure_intermediary_subcommand(&mut eval_cmd);
+    configure_intermediary_subcommand(&mut tool_cmd);
+    let path = cfg
+        .output
+        .as_ref()
+        .map(|dir| log_subpath(dir, eval_string, tool_string));
+    // Estimate progress from the log of the last run, if any, before overwriting it.
+    let progress = path.as_deref().and_then(read_progress);
+    let options = intermediary::Options {
+        progress,
+        supported: expected.map(|outcome| outcome != Some(BadOutcome::Undefined)),
+        settings: settings.cloned(),
+        host: path.is_some().then(|| host::host([&eval_cmd, &tool_cmd])),
+        containers: vec![container],
+        scratch,
+        ..options.clone()
+    };
+    let events = match &path {
+        Some(path) if cfg.events => {
+            let path = events_path(path);
+            let file = fs::File::create(&path).with_context(|| format!("{}", path.display()))?;
+            intermediary::Events::File(Arc::new(file))
         }
+        _ => intermediary::Events::None,
+    };
+    let options = intermediary::Options { events, ..options };
+    let logs = path
+        .map(|path| intermediary::Logs::create(&path, 0))
+        .transpose()?;
+    if logs.is_some() {
+        eval_cmd.stderr(Stdio::piped());
+        tool_cmd.stderr(Stdio::piped());
+    }
+    let outcome = match (eval_cmd.spawn(), tool_cmd.spawn()) {
+        (Ok(mut eval_child), Ok(mut tool_child)) => {
+            let eval = intermediary::Eval::Process(&mut eval_child);
+            let tools = std::slice::from_mut(&mut tool_child);
+            intermediary::run(ctrl_c, out.clone(), logs, eval, tools, &options)
+        }
+        _ => Err(BadOutcome::Error),
+    };
+    write!(out, "{} ", " outcome".bold())?;
+    let actual = match outcome {
+        Ok(()) => "success",
+        Err(BadOutcome::Interrupt) => {
+            writeln!(out, "interrupt")?;
+            return Ok(PairResult::Interrupt);
+        }
+        Err(bad_outcome) => <&str>::from(bad_outcome),
+    };
+    writeln!(out, "{actual}")?;
+    if !cfg.check {
+        return Ok(PairResult::Pass);
     }
-    Ok(if pass { Ok(()) } else { Err(ExitCode::FAILURE) })
+    let expected = expected.map(|o| match o {
+        Some(bad_outcome) => <&str>::from(bad_outcome),
+        None => "success",
+    });
+    Ok(match expected {
+        Some(o) => {
+            if actual == o {
+                writeln!(out, "{} {}", "expected".green().bold(), o.green())?;
+                PairResult::Pass
+            } else {
+                writeln!(out, "{} {}", "expected".red().bold(), o.red())?;
+                PairResult::Fail
+            }
+        }
+        None => {
+            writeln!(out, "{} {}", "expected".yellow().bold(), "unknown".yellow())?;
+            PairResult::Fail
+        }
+    })
 }
 
 /// Print a JSON `value` with a `name` for GitHub Actions.
@@ -1253,19 +1832,80 @@ fn matrix() -> anyhow::Result<()> {
 }
 
 /// Run a subcommand from the "Log" command group.
-fn log_command(command: LogCommands) -> anyhow::Result<()> {
+fn log_command(ctrl_c: &CtrlC, command: LogCommands) -> Result<(), ExitCode> {
     match command {
         LogCommands::Trim { input, output } => {
-            run_in_out(log::Trim, input.as_deref(), output.as_deref())
+            run_in_out(log::Trim, input.as_deref(), output.as_deref()).map_err(err_fail)
+        }
+        LogCommands::Summary { input } => {
+            run_in_out(log::Summary, input.as_deref(), None).map_err(err_fail)
+        }
+        LogCommands::Flatten { input, output } => log::flatten(&input, &output).map_err(err_fail),
+        LogCommands::MockTool { input, delays } => open_log(&input)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut log| mock::MockTool::new(&mut log, delays))
+            .with_context(|| format!("{}", input.display()))
+            .and_then(|mock| mock.run(io::stdin(), io::stdout()))
+            .map_err(err_fail),
+        LogCommands::Replay {
+            input,
+            tool,
+            output,
+            timeout,
+            tolerance,
+        } => {
+            let replay = open_log(&input)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut log| {
+                    let tolerance = Tolerance {
+                        absolute: tolerance,
+                        relative: tolerance,
+                    };
+                    replay::Replay::new(&mut log, tolerance)
+                })
+                .with_context(|| format!("{}", input.display()))
+                .map_err(err_fail)?;
+            let logs = output
+                .as_deref()
+                .map(|path| intermediary::Logs::create(path, 0))
+                .transpose()
+                .map_err(|err| err_fail(anyhow!(err)))?;
+            let mut container = None;
+            let mut tool_child = shell(&tool)
+                .and_then(|mut cmd| {
+                    container = container_id_file(&mut cmd)?;
+                    configure_intermediary_subcommand(&mut cmd);
+                    if logs.is_some() {
+                        cmd.stderr(Stdio::piped());
+                    }
+                    Ok(cmd.spawn()?)
+                })
+                .context("tool")
+                .map_err(err_fail)?;
+            let options = intermediary::Options {
+                timeout: timeout.map(Duration::from_secs),
+                host: output
+                    .is_some()
+                    .then(|| host::host(shell(&tool).ok().as_ref())),
+                containers: vec![container],
+                ..Default::default()
+            };
+            intermediary::run(
+                ctrl_c,
+                Console::stdout(),
+                logs,
+                intermediary::Eval::Replay(replay),
+                std::slice::from_mut(&mut tool_child),
+                &options,
+            )
+            .map_err(ExitCode::from)
         }
-        LogCommands::Summary { input } => run_in_out(log::Summary, input.as_deref(), None),
-        LogCommands::Flatten { input, output } => log::flatten(&input, &output),
     }
 }
 
 /// Run the GradBench CLI, returning a `Result`.
 fn cli() -> Result<(), ExitCode> {
-    let mut ctrl_c = CtrlC::new().map_err(|error| err_fail(anyhow!(error)))?;
+    let ctrl_c = CtrlC::new().map_err(|error| err_fail(anyhow!(error)))?;
     match Cli::parse().command {
         Commands::Eval {
             eval,
@@ -1294,45 +1934,148 @@ fn cli() -> Result<(), ExitCode> {
             tool,
             output,
             timeout,
+            start_timeout,
+            define_timeout,
+            evaluate_timeout,
+            session_timeout,
+            grace,
+            cpus,
+            threads,
+            strict,
+            echo_stderr,
+            lenient_stdout,
+            scratch,
+            validate_schema,
+            reference,
+            reference_tolerance,
+            atol,
+            rtol,
+            events,
+            events_output,
+            progress_from,
         } => {
-            let log_file = output
-                .map(fs::File::create)
+            // Read this first, in case it's the same file as the output log.
+            let progress = progress_from
+                .map(|path| {
+                    open_log(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|mut log| intermediary::Progress::read(&mut log))
+                        .with_context(|| format!("{}", path.display()))
+                })
+                .transpose()
+                .map_err(err_fail)?;
+            let events = match (events, events_output) {
+                (None, _) => intermediary::Events::None,
+                (Some(_), None) => intermediary::Events::Stdout,
+                (Some(_), Some(path)) => fs::File::create(&path)
+                    .map(|file| intermediary::Events::File(Arc::new(file)))
+                    .with_context(|| format!("{}", path.display()))
+                    .map_err(err_fail)?,
+            };
+            let logs = output
+                .as_deref()
+                .map(|path| intermediary::Logs::create(path, tool.len() - 1))
                 .transpose()
                 .map_err(|err| err_fail(anyhow!(err)))?;
+            let scratch = scratch
+                .as_deref()
+                .map(scratch_dir)
+                .transpose()
+                .map_err(err_fail)?;
+            let tolerance = Tolerance {
+                absolute: atol,
+                relative: rtol,
+            };
+            let golden = reference
+                .map(|path| {
+                    open_log(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|mut log| {
+                            golden::Golden::read(&mut log, tolerance, reference_tolerance)
+                        })
+                        .map(Arc::new)
+                        .with_context(|| format!("{}", path.display()))
+                })
+                .transpose()
+                .map_err(err_fail)?;
+            let schemas = validate_schema
+                .map(|dir| schema::Schemas::read(&dir).map(Arc::new))
+                .transpose()
+                .map_err(err_fail)?;
             let mut eval_child = shell(&eval)
                 .and_then(|mut cmd| {
+                    if let Some(dir) = &scratch {
+                        share(&mut cmd, dir);
+                    }
                     configure_intermediary_subcommand(&mut cmd);
+                    if logs.is_some() {
+                        cmd.stderr(Stdio::piped());
+                    }
                     Ok(cmd.spawn()?)
                 })
                 .context("eval")
                 .map_err(err_fail)?;
-            let mut tool_child = shell(&tool)
-                .and_then(|mut cmd| {
-                    configure_intermediary_subcommand(&mut cmd);
-                    Ok(cmd.spawn()?)
+            let settings = tool_settings(cpus, threads);
+            let tool_children = tool
+                .iter()
+                .map(|tool| {
+                    shell(tool)
+                        .and_then(|mut cmd| {
+                            if let Some(settings) = &settings {
+                                constrain(&mut cmd, settings)?;
+                            }
+                            if let Some(dir) = &scratch {
+                                share(&mut cmd, dir);
+                            }
+                            let container = container_id_file(&mut cmd)?;
+                            configure_intermediary_subcommand(&mut cmd);
+                            if logs.is_some() {
+                                cmd.stderr(Stdio::piped());
+                            }
+                            Ok((cmd.spawn()?, container))
+                        })
+                        .context("tool")
                 })
-                .context("tool")
+                .collect::<anyhow::Result<Vec<_>>>()
                 .map_err(err_fail)?;
-            let timeout = timeout.map(Duration::from_secs);
-            let outcome = intermediary::run(
-                &mut ctrl_c,
-                log_file,
-                &mut eval_child,
-                &mut tool_child,
-                timeout,
-            );
-            let eval_wait = eval_child.wait();
-            let tool_wait = tool_child.wait();
-            match outcome {
-                Ok(()) => {
-                    if eval_wait.is_ok() && tool_wait.is_ok() {
-                        Ok(())
-                    } else {
-                        Err(ExitCode::FAILURE)
-                    }
-                }
-                Err(bad_outcome) => Err(ExitCode::from(bad_outcome)),
-            }
+            let (mut tool_children, containers): (Vec<_>, Vec<_>) =
+                tool_children.into_iter().unzip();
+            let options = intermediary::Options {
+                timeout: timeout.map(Duration::from_secs),
+                start_timeout: start_timeout.map(Duration::from_secs),
+                define_timeout: define_timeout.map(Duration::from_secs),
+                evaluate_timeout: evaluate_timeout.map(Duration::from_secs),
+                session_timeout: session_timeout.map(Duration::from_secs),
+                grace: grace.map(Duration::from_secs),
+                strict,
+                echo_stderr,
+                lenient_stdout,
+                tolerance,
+                events,
+                progress,
+                supported: None,
+                settings,
+                scratch,
+                schemas,
+                golden,
+                containers,
+                host: output.is_some().then(|| {
+                    let cmds: Vec<_> = std::iter::once(&eval)
+                        .chain(&tool)
+                        .filter_map(|cmd| shell(cmd).ok())
+                        .collect();
+                    host::host(&cmds)
+                }),
+            };
+            intermediary::run(
+                &ctrl_c,
+                Console::stdout(),
+                logs,
+                intermediary::Eval::Process(&mut eval_child),
+                &mut tool_children,
+                &options,
+            )
+            .map_err(ExitCode::from)
         }
         Commands::ExitCode { outcome } => match BadOutcome::from_str(&outcome) {
             Ok(bad_outcome) => Err(bad_outcome.into()),
@@ -1354,16 +2097,44 @@ fn cli() -> Result<(), ExitCode> {
                     no_tool,
                     output,
                     timeout,
+                    start_timeout,
+                    define_timeout,
+                    evaluate_timeout,
+                    session_timeout,
+                    grace,
+                    cpus,
+                    threads,
+                    strict,
+                    echo_stderr,
+                    lenient_stdout,
+                    scratch,
                     check,
                     download_github,
+                    events,
+                    jobs,
+                    partition_cpus,
                     dry_run,
                 } => match run_multiple(
-                    &mut ctrl_c,
+                    &ctrl_c,
                     RunConfig {
                         output,
                         timeout,
+                        start_timeout,
+                        define_timeout,
+                        evaluate_timeout,
+                        session_timeout,
+                        grace,
+                        cpus,
+                        threads,
+                        strict,
+                        echo_stderr,
+                        lenient_stdout,
+                        scratch,
                         check,
                         download_github,
+                        events: events.is_some(),
+                        jobs,
+                        partition_cpus,
                     },
                     RunRaw {
                         eval,
@@ -1465,7 +2236,7 @@ fn cli() -> Result<(), ExitCode> {
                 }
             }
         }
-        Commands::Log { command } => log_command(command).map_err(err_fail),
+        Commands::Log { command } => log_command(&ctrl_c, command),
     }
 }
 
@@ -1479,7 +2250,13 @@ pub fn main() -> ExitCode {
 
 #[cfg(test)]
 mod tests {
-    use std::{fs, io::Write, path::Path, process::ExitCode};
+    use std::{
+        fs,
+        io::Write,
+        num::NonZeroUsize,
+        path::{Path, PathBuf},
+        process::ExitCode,
+    };
 
     use goldenfile::Mint;
     use pretty_assertions::assert_eq;
@@ -1711,6 +2488,99 @@ mod tests {
         simple_dry_run(&mut stdout, &[], &[], cfg);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_phase_timeouts() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_phase_timeouts.sh").unwrap();
+        let cfg = RunConfig {
+            start_timeout: Some(60),
+            define_timeout: Some(600),
+            evaluate_timeout: Some(10),
+            session_timeout: Some(3600),
+            grace: Some(5),
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &[], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_settings() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_settings.sh").unwrap();
+        let cfg = RunConfig {
+            cpus: Some("0-3".to_string()),
+            threads: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &[], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_events() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_events.sh").unwrap();
+        let cfg = RunConfig {
+            output: Some(PathBuf::from("run")),
+            events: true,
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &["hello"], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_partition_cpus() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_partition_cpus.sh").unwrap();
+        let cfg = RunConfig {
+            cpus: Some("0-7".to_string()),
+            jobs: NonZeroUsize::new(3),
+            partition_cpus: true,
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &["hello"], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_scratch() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_scratch.sh").unwrap();
+        let cfg = RunConfig {
+            scratch: Some(PathBuf::from("/tmp/scratch")),
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &[], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_strict() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_strict.sh").unwrap();
+        let cfg = RunConfig {
+            strict: true,
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &[], &[], cfg);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_dry_echo_stderr() {
+        let mut mint = Mint::new("src/outputs");
+        let mut stdout = mint.new_goldenfile("dry_run_echo_stderr.sh").unwrap();
+        let cfg = RunConfig {
+            output: Some(PathBuf::from("run")),
+            echo_stderr: true,
+            ..Default::default()
+        };
+        simple_dry_run(&mut stdout, &[], &[], cfg);
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_run_dry_download_github() {
@@ -0,0 +1,223 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io, thread,
+    time::Duration,
+};
+
+use anyhow::bail;
+
+use serde_json::{json, Value};
+
+use crate::{
+    protocol::{LogMessage, LogResponse, Message},
+    util::{nanos_duration, try_read_line, InOut},
+};
+
+/// A recorded response, along with how long the tool took to send it.
+#[derive(Clone)]
+struct Recorded {
+    /// The response, whose ID gets replaced to match each new message.
+    response: Value,
+
+    /// The time between the message and the response.
+    delay: Duration,
+}
+
+/// Copy a JSON `value` with every number as a float, so that inputs like `3` and `3.0` are the same.
+fn normalize(value: &Value) -> Value {
+    match value {
+        Value::Number(number) => number
+            .as_f64()
+            .and_then(serde_json::Number::from_f64)
+            .map_or_else(|| value.clone(), Value::Number),
+        Value::Array(values) => Value::Array(values.iter().map(normalize).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), normalize(value)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Get the key for looking up the recorded response to a message, or `None` if the response to that
+/// kind of message doesn't depend on what the tool did.
+///
+/// The input of an `"evaluate"` message is normalized, and objects are serialized with their keys
+/// in order, so equal inputs get the same key however they were written.
+fn key(message: &Message) -> Option<String> {
+    match message {
+        Message::Start { .. } => Some(json!(["start"]).to_string()),
+        Message::Define { module, .. } => Some(json!(["define", module]).to_string()),
+        Message::Evaluate {
+            module,
+            function,
+            input,
+            ..
+        } => Some(json!(["evaluate", module, function, input.as_ref().map(normalize)]).to_string()),
+        Message::Analysis { .. } => None,
+        Message::End { .. } => Some(json!(["end"]).to_string()),
+        Message::Cancel { .. } => None,
+    }
+}
+
+/// A stand-in for a tool, which responds to messages with the responses recorded in a log file.
+///
+/// Each message is matched with a recorded message of the same kind for the same module, function,
+/// and input. If the same message was recorded several times, the recorded responses are used in
+/// order, reusing the last one once they run out.
+pub struct MockTool {
+    /// The recorded responses for each key.
+    recorded: HashMap<String, VecDeque<Recorded>>,
+
+    /// Whether to wait as long as the recorded tool took before sending each response.
+    delays: bool,
+}
+
+impl MockTool {
+    /// Read the messages and responses from a `log` file, which must not have been trimmed.
+    ///
+    /// A log from a session that passed inputs or outputs in files with the `"refs"` feature is
+    /// refused, since the eval deletes those files after each round.
+    pub fn new(log: &mut impl io::BufRead, delays: bool) -> anyhow::Result<Self> {
+        let mut recorded = HashMap::<String, VecDeque<Recorded>>::new();
+        let mut message = None;
+        while let Some(line) = try_read_line(log)? {
+            if let Ok(entry) = serde_json::from_str::<LogMessage>(&line) {
+                if let Message::Evaluate {
+                    id,
+                    input_ref: Some(_),
+                    ..
+                } = &entry.message
+                {
+                    bail!(
+                        "evaluate message {id} has its input in a file, not the log, so a \
+                         session that used the \"refs\" feature can't be mocked"
+                    );
+                }
+                message = Some(entry);
+            } else if let Ok(entry) = serde_json::from_str::<LogResponse<Value>>(&line) {
+                let Some(LogMessage { elapsed, message }) = message.take() else {
+                    continue;
+                };
+                if entry.response.get("output_ref").is_some() {
+                    let id = message.id();
+                    bail!(
+                        "evaluate response {id} has its output in a file, not the log, so a \
+                         session that used the \"refs\" feature can't be mocked"
+                    );
+                }
+                if let Some(key) = key(&message) {
+                    let nanoseconds = entry
+                        .elapsed
+                        .nanoseconds
+                        .saturating_sub(elapsed.nanoseconds);
+                    recorded.entry(key).or_default().push_back(Recorded {
+                        response: entry.response,
+                        delay: nanos_duration(nanoseconds)?,
+                    });
+                }
+            }
+        }
+        Ok(Self { recorded, delays })
+    }
+
+    /// Respond to a message.
+    fn respond(&mut self, message: &Message) -> Value {
+        let id = message.id();
+        let recorded = key(message).and_then(|key| {
+            let queue = self.recorded.get_mut(&key)?;
+            if queue.len() > 1 {
+                queue.pop_front()
+            } else {
+                queue.front().cloned()
+            }
+        });
+        match recorded {
+            Some(Recorded {
+                mut response,
+                delay,
+            }) => {
+                if self.delays {
+                    thread::sleep(delay);
+                }
+                response["id"] = json!(id);
+                response
+            }
+            None => match message {
+                Message::Start { .. } | Message::Analysis { .. } | Message::End { .. } => {
+                    json!({ "id": id })
+                }
+                Message::Cancel { .. } => json!({ "id": id, "cancelled": true }),
+                Message::Define { module, .. } => json!({
+                    "id": id,
+                    "success": false,
+                    "error": format!("no recorded response to define {module:?}"),
+                }),
+                Message::Evaluate {
+                    module, function, ..
+                } => json!({
+                    "id": id,
+                    "success": false,
+                    "error": format!("no recorded response to evaluate {module}::{function} on this input"),
+                }),
+            },
+        }
+    }
+}
+
+impl InOut<anyhow::Result<()>> for MockTool {
+    fn run(mut self, read: impl io::Read, mut out: impl io::Write) -> anyhow::Result<()> {
+        let input = &mut io::BufReader::new(read);
+        while let Some(line) = try_read_line(input)? {
+            let message: Message = serde_json::from_str(&line)?;
+            writeln!(out, "{}", self.respond(&message))?;
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use goldenfile::Mint;
+    use serde_json::{json, Value};
+
+    use crate::{mock::MockTool, util::InOut};
+
+    #[test]
+    fn test_mock_tool() -> anyhow::Result<()> {
+        let log = include_str!("inputs/log/mock_tool.jsonl");
+        let messages = include_str!("inputs/eval/mock_tool.jsonl");
+        let mut output = Vec::new();
+        MockTool::new(&mut log.as_bytes(), false)?.run(messages.as_bytes(), &mut output)?;
+        let mut mint = Mint::new("src/outputs");
+        let mut file = mint.new_goldenfile("mock_tool.jsonl")?;
+        file.write_all(&output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_tool_normalized_input() -> anyhow::Result<()> {
+        let log = r#"{ "elapsed": { "nanoseconds": 0 }, "message": {"id": 0, "kind": "evaluate", "module": "m", "function": "f", "input": {"x": 3, "y": [1.5]}} }
+{ "elapsed": { "nanoseconds": 1 }, "response": {"id": 0, "success": true, "output": 9} }
+"#;
+        let message = r#"{"id": 5, "kind": "evaluate", "module": "m", "function": "f", "input": {"y": [1.5], "x": 3.0}}"#;
+        let mut output = Vec::new();
+        MockTool::new(&mut log.as_bytes(), false)?.run(message.as_bytes(), &mut output)?;
+        let response: Value = serde_json::from_slice(&output)?;
+        assert_eq!(response, json!({ "id": 5, "success": true, "output": 9 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_mock_tool_refs() {
+        let log = r#"{ "elapsed": { "nanoseconds": 0 }, "message": {"id": 0, "kind": "evaluate", "module": "m", "function": "f", "input": 3} }
+{ "elapsed": { "nanoseconds": 1 }, "response": {"id": 0, "success": true, "output_ref": "output-0.json"} }
+"#;
+        let err = MockTool::new(&mut log.as_bytes(), false).err().unwrap();
+        assert!(err.to_string().contains("\"refs\" feature can't be mocked"));
+    }
+}
@@ -0,0 +1,12 @@
+docker build . --file evals/norf/Dockerfile --tag ghcr.io/gradbench/eval-norf:latest
+docker build . --file evals/qux/Dockerfile --tag ghcr.io/gradbench/eval-qux:latest
+docker build . --file tools/bar/Dockerfile --tag ghcr.io/gradbench/tool-bar:latest
+docker build . --file tools/baz/Dockerfile --tag ghcr.io/gradbench/tool-baz:latest
+docker build . --file tools/foo/Dockerfile --tag ghcr.io/gradbench/tool-foo:latest
+mkdir -p run/norf run/qux
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-norf:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-bar:latest' -o run/norf/bar.jsonl
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-norf:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-baz:latest' -o run/norf/baz.jsonl
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-norf:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-foo:latest' -o run/norf/foo.jsonl
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-qux:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-bar:latest' -o run/qux/bar.jsonl
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-qux:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-baz:latest' -o run/qux/baz.jsonl
+gradbench run --echo-stderr --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-qux:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-foo:latest' -o run/qux/foo.jsonl
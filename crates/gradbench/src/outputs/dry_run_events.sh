@@ -0,0 +1,8 @@
+docker build . --file evals/hello/Dockerfile --tag ghcr.io/gradbench/eval-hello:latest
+docker build . --file tools/bar/Dockerfile --tag ghcr.io/gradbench/tool-bar:latest
+docker build . --file tools/baz/Dockerfile --tag ghcr.io/gradbench/tool-baz:latest
+docker build . --file tools/foo/Dockerfile --tag ghcr.io/gradbench/tool-foo:latest
+mkdir -p run/hello
+gradbench run --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-bar:latest' -o run/hello/bar.jsonl --events json --events-output run/hello/bar.events.jsonl
+gradbench run --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-baz:latest' -o run/hello/baz.jsonl --events json --events-output run/hello/baz.events.jsonl
+gradbench run --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-foo:latest' -o run/hello/foo.jsonl --events json --events-output run/hello/foo.events.jsonl
@@ -0,0 +1,7 @@
+docker build . --file evals/hello/Dockerfile --tag ghcr.io/gradbench/eval-hello:latest
+docker build . --file tools/bar/Dockerfile --tag ghcr.io/gradbench/tool-bar:latest
+docker build . --file tools/baz/Dockerfile --tag ghcr.io/gradbench/tool-baz:latest
+docker build . --file tools/foo/Dockerfile --tag ghcr.io/gradbench/tool-foo:latest
+gradbench run --cpus 0-2 --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-bar:latest'
+gradbench run --cpus 3-5 --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-baz:latest'
+gradbench run --cpus 6-7 --eval 'docker run --rm --interactive ghcr.io/gradbench/eval-hello:latest' --tool 'docker run --rm --interactive ghcr.io/gradbench/tool-foo:latest'
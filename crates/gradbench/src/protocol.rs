@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, fmt};
+
 use serde::{Deserialize, Deserializer, Serialize};
 
 /// Deserialize an optional JSON value as `Some`, so only missing values become `None`.
@@ -11,6 +13,16 @@ fn deserialize_optional_json<'de, D: Deserializer<'de>>(
 /// A message ID.
 pub type Id = i64;
 
+/// The protocol version assumed for an eval or tool that doesn't give one.
+pub const DEFAULT_PROTOCOL_VERSION: u32 = 1;
+
+/// The latest protocol version that the intermediary understands.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The optional protocol features that the intermediary provides itself, so the tool can use them
+/// even if the eval doesn't list them.
+pub const INTERMEDIARY_FEATURES: &[&str] = &["end", "cancel"];
+
 /// A message from the eval.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
@@ -34,6 +46,14 @@ pub enum Message {
             skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
         )]
         config: Option<serde_json::Value>,
+
+        /// The latest protocol version that the eval supports.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        protocol_version: Option<u32>,
+
+        /// The optional protocol features that the eval supports.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        features: Option<Vec<String>>,
     },
 
     /// A request to define a module.
@@ -43,6 +63,14 @@ pub enum Message {
 
         /// The name of the module.
         module: String,
+
+        /// An optional time limit in seconds for the tool to respond, which the intermediary uses in
+        /// place of its own if it is shorter.
+        #[serde(
+            default, // Deserialize as `None` if missing.
+            skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+        )]
+        timeout: Option<f64>,
     },
 
     /// A request to evaluate a function.
@@ -64,12 +92,28 @@ pub enum Message {
         )]
         input: Option<serde_json::Value>,
 
+        /// The path of a file in the scratch directory holding the input as JSON, in place of
+        /// `input`, if the eval and tool agreed on the `"refs"` feature.
+        #[serde(
+            default, // Deserialize as `None` if missing.
+            skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+        )]
+        input_ref: Option<String>,
+
         /// An optional and short human-readable description of the input.
         #[serde(
             default, // Deserialize as `None` if missing.
             skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
         )]
         description: Option<String>,
+
+        /// An optional time limit in seconds for the tool to respond, which the intermediary uses in
+        /// place of its own if it is shorter.
+        #[serde(
+            default, // Deserialize as `None` if missing.
+            skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+        )]
+        timeout: Option<f64>,
     },
 
     /// Analysis results from evaluating a function.
@@ -90,6 +134,61 @@ pub enum Message {
         )]
         error: Option<String>,
     },
+
+    /// The last message, sent by the intermediary once the eval is done, if the tool supports the
+    /// `"end"` feature.
+    End {
+        /// The message ID.
+        id: Id,
+    },
+
+    /// A request for the tool to give up on the `"evaluate"` message with the same ID, sent by the
+    /// intermediary when it times out, if the tool supports the `"cancel"` feature.
+    Cancel {
+        /// The ID of the message to give up on.
+        id: Id,
+    },
+}
+
+impl Message {
+    /// Get the message ID.
+    pub fn id(&self) -> Id {
+        match self {
+            Message::Start { id, .. }
+            | Message::Define { id, .. }
+            | Message::Evaluate { id, .. }
+            | Message::Analysis { id, .. }
+            | Message::End { id }
+            | Message::Cancel { id } => *id,
+        }
+    }
+
+    /// Get the name of the message kind, as it appears in JSON.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::Start { .. } => "start",
+            Message::Define { .. } => "define",
+            Message::Evaluate { .. } => "evaluate",
+            Message::Analysis { .. } => "analysis",
+            Message::End { .. } => "end",
+            Message::Cancel { .. } => "cancel",
+        }
+    }
+
+    /// Get the time limit in seconds that the eval suggested for the response, if any.
+    pub fn timeout(&self) -> Option<f64> {
+        match self {
+            Message::Define { timeout, .. } | Message::Evaluate { timeout, .. } => *timeout,
+            _ => None,
+        }
+    }
+}
+
+/// The fields common to every response from the tool.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BaseResponse {
+    /// The message ID.
+    pub id: Id,
 }
 
 /// Nanosecond timings from the tool.
@@ -102,6 +201,10 @@ pub struct Timing {
     pub nanoseconds: u128,
 }
 
+/// The modules that a tool implements, each with the functions that it implements in that module, or
+/// `None` for all of them.
+pub type Modules = BTreeMap<String, Option<Vec<String>>>;
+
 /// A response from the tool to a `"start"` message.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct StartResponse {
@@ -122,6 +225,156 @@ pub struct StartResponse {
         skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
     )]
     pub config: Option<serde_json::Value>,
+
+    /// The protocol version that the tool chose, which must be supported by the eval.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u32>,
+
+    /// The optional protocol features that the tool supports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+
+    /// The modules that the tool implements, if it says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modules: Option<Modules>,
+}
+
+/// The protocol that the eval and tool agreed on in the `"start"` round.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Protocol {
+    /// The protocol version.
+    pub version: u32,
+
+    /// The optional features that both the eval and tool support, or that the tool supports and
+    /// the intermediary provides.
+    pub features: Vec<String>,
+}
+
+impl Protocol {
+    /// Agree on a protocol from the `version` and `features` in a `"start"` message and the tool's
+    /// `response`, or describe why the eval and tool are incompatible.
+    pub fn negotiate(
+        version: Option<u32>,
+        features: Option<&[String]>,
+        response: &StartResponse,
+    ) -> Result<Self, String> {
+        let supported = version.unwrap_or(DEFAULT_PROTOCOL_VERSION);
+        let chosen = response
+            .protocol_version
+            .unwrap_or(DEFAULT_PROTOCOL_VERSION);
+        if chosen == 0 {
+            return Err("tool chose protocol version 0, but versions start at 1".to_string());
+        }
+        if chosen > supported {
+            return Err(format!(
+                "tool chose protocol version {chosen}, but the eval only supports up to version \
+                 {supported}"
+            ));
+        }
+        if chosen > PROTOCOL_VERSION {
+            return Err(format!(
+                "tool chose protocol version {chosen}, but the intermediary only supports up to \
+                 version {PROTOCOL_VERSION}"
+            ));
+        }
+        let offered = features.unwrap_or_default();
+        let features = match &response.features {
+            Some(accepted) => accepted
+                .iter()
+                .filter(|feature| {
+                    offered.contains(feature) || INTERMEDIARY_FEATURES.contains(&feature.as_str())
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(Self {
+            version: chosen,
+            features,
+        })
+    }
+
+    /// Check whether the eval and tool agreed to use a `feature`.
+    pub fn has(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+}
+
+/// A log entry recording the protocol that the eval and tool agreed on, after the `"start"` round.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogProtocol {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The agreed protocol.
+    pub protocol: Protocol,
+}
+
+/// The machine and software that a session ran on, to tell apart results from different hosts.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Host {
+    /// The hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    /// The CPU model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<String>,
+
+    /// The number of CPU cores available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cores: Option<usize>,
+
+    /// The operating system kernel and its release.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel: Option<String>,
+
+    /// The total memory in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<u64>,
+
+    /// The version of the GradBench CLI.
+    pub gradbench: String,
+
+    /// The Git commit checked out in the current directory, if it is in a repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// The ID of each Docker image run by the eval or tools, by image name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub images: BTreeMap<String, String>,
+}
+
+/// A log entry recording the host that the session ran on, as the first line of the log.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogHost {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The host.
+    pub host: Host,
+}
+
+/// Settings that the intermediary applied to the tool processes to make timings less noisy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Settings {
+    /// The CPUs that the tools were pinned to, as a list like `0-3,6`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<String>,
+
+    /// Environment variables set for the tools, such as to limit their number of threads.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+/// A log entry recording the settings applied to the tools, at the start of the log.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogSettings {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The applied settings.
+    pub settings: Settings,
 }
 
 /// A response from the tool to a `"define"` message.
@@ -165,6 +418,14 @@ pub struct EvaluateResponse {
     )]
     pub output: Option<serde_json::Value>,
 
+    /// The path of a file in the scratch directory holding the output as JSON, in place of
+    /// `output`, if the eval and tool agreed on the `"refs"` feature.
+    #[serde(
+        default, // Deserialize as `None` if missing.
+        skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+    )]
+    pub output_ref: Option<String>,
+
     /// Subtask timings.
     pub timings: Option<Vec<Timing>>,
 
@@ -183,6 +444,39 @@ pub struct AnalysisResponse {
     pub id: Id,
 }
 
+/// A response from the tool to an `"end"` message.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EndResponse {
+    /// The message ID.
+    pub id: Id,
+
+    /// Timings for the session as a whole, such as time spent outside of any evaluation.
+    #[serde(
+        default, // Deserialize as `None` if missing.
+        skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+    )]
+    pub timings: Option<Vec<Timing>>,
+
+    /// Any other final measurements, such as counters or the location of a profile.
+    #[serde(
+        default, // Deserialize as `None` if missing.
+        deserialize_with = "deserialize_optional_json", // Deserialize as `Some` if present.
+        skip_serializing_if = "Option::is_none" // Serialize as missing if `None`.
+    )]
+    pub metrics: Option<serde_json::Value>,
+}
+
+/// A response from the tool to a `"cancel"` message, which can't be mistaken for a late response to
+/// the `"evaluate"` message that it cancels.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CancelResponse {
+    /// The message ID.
+    pub id: Id,
+
+    /// Always true, to tell this apart from a response to the `"evaluate"` message.
+    pub cancelled: bool,
+}
+
 /// A nanoseconds object.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Nanoseconds {
@@ -208,3 +502,264 @@ pub struct LogResponse<T> {
     /// The contained response.
     pub response: T,
 }
+
+/// A phase of a session, for reporting which timeout was exceeded.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Responding to a `"start"` message.
+    Start,
+
+    /// Responding to a `"define"` message.
+    Define,
+
+    /// Responding to an `"evaluate"` message.
+    Evaluate,
+
+    /// Responding to an `"analysis"` message.
+    Analysis,
+
+    /// Responding to the `"end"` message.
+    End,
+
+    /// Responding to a `"cancel"` message.
+    Cancel,
+
+    /// The session as a whole.
+    Session,
+}
+
+impl Phase {
+    /// Get the name of the phase, as it appears in JSON.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Start => "start",
+            Phase::Define => "define",
+            Phase::Evaluate => "evaluate",
+            Phase::Analysis => "analysis",
+            Phase::End => "end",
+            Phase::Cancel => "cancel",
+            Phase::Session => "session",
+        }
+    }
+}
+
+/// A description of a timeout that ended a session.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Timeout {
+    /// The phase whose time limit was exceeded.
+    pub phase: Phase,
+
+    /// The time limit in nanoseconds.
+    pub nanoseconds: u128,
+}
+
+/// A timeout entry in a log file, in place of the response that never arrived.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogTimeout {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The timeout that occurred.
+    pub timeout: Timeout,
+}
+
+/// A description of how a process exited, such as a tool that stopped responding in the middle of a
+/// session.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Crash {
+    /// The exit status of the tool, if it exited normally.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+
+    /// The signal that terminated the tool, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.status, self.signal) {
+            (_, Some(signal)) => {
+                write!(f, "killed by signal {signal}")?;
+                #[cfg(unix)]
+                if let Ok(name) = nix::sys::signal::Signal::try_from(signal) {
+                    write!(f, " ({name})")?;
+                }
+                Ok(())
+            }
+            (Some(status), None) => write!(f, "exit status {status}"),
+            (None, None) => write!(f, "unknown exit status"),
+        }
+    }
+}
+
+/// An entry in a log file after the response to an `"evaluate"` message that timed out, saying that
+/// the tool cancelled it and the response was made up by the intermediary.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogCancelled {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The timeout that occurred.
+    pub cancelled: Timeout,
+}
+
+/// An entry in a log file after a response that the intermediary made up on the tool's behalf,
+/// because the tool's `"start"` response said it doesn't implement that module or function.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogUnimplemented {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The ID of the message that the response is to.
+    pub unimplemented: Id,
+}
+
+/// An entry in a log file for a line that the tool printed to stdout which was not a JSON object
+/// with an `"id"`, and was skipped over because of the `--lenient-stdout` flag.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogNoise {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The line, without its trailing newline.
+    pub noise: String,
+}
+
+/// A file in the scratch directory that the eval or tool passed by reference, instead of inline.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Reference {
+    /// The path of the file, relative to the scratch directory.
+    pub path: String,
+
+    /// The size of the file.
+    pub bytes: u64,
+
+    /// The SHA-256 hash of the file contents, in hexadecimal.
+    pub sha256: String,
+}
+
+/// An entry in a log file for a file passed by reference, in place of its contents.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogReference {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The file.
+    pub reference: Reference,
+}
+
+/// The result of comparing the output of an evaluation to that of a known-good session.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GoldenCheck {
+    /// The ID of the evaluate message.
+    pub of: Id,
+
+    /// Whether the output matched the known-good one.
+    pub valid: bool,
+
+    /// The first difference found, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// An entry in a log file after the response to an `"evaluate"` message, for comparing its output
+/// to that of the session given by the `--reference` flag.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogGolden {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The result of the comparison.
+    pub golden: GoldenCheck,
+}
+
+/// A crash entry in a log file, in place of the response that never arrived.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogCrash {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// How the tool exited.
+    pub crash: Crash,
+}
+
+/// Resource usage of the tool process over a whole session, including any of its descendants that
+/// it waited for.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Usage {
+    /// The peak resident set size in bytes.
+    pub max_rss_bytes: u64,
+
+    /// The CPU time spent in user mode.
+    pub user: Nanoseconds,
+
+    /// The CPU time spent in the kernel.
+    pub system: Nanoseconds,
+
+    /// The number of times the tool gave up the CPU, for instance to wait for I/O, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voluntary_context_switches: Option<u64>,
+
+    /// The number of times the tool was preempted, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub involuntary_context_switches: Option<u64>,
+}
+
+/// A resource usage entry at the end of a log file.
+///
+/// For a tool run with `docker run`, whose process is only the Docker client, this is the usage of
+/// its container instead, if it could be read.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogUsage {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// The resource usage of the tool.
+    pub usage: Usage,
+}
+
+/// How a session ended, as recorded once it is over.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Footer {
+    /// The outcome of the session, as given to `gradbench exit-code`, or `"success"`.
+    pub outcome: String,
+
+    /// How the eval exited, if it was a process that could be waited for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<Crash>,
+
+    /// How the tool exited, if it could be waited for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<Crash>,
+}
+
+/// The footer entry which is the last line of every complete log file.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogFooter {
+    /// The timestamp in nanoseconds.
+    pub elapsed: Nanoseconds,
+
+    /// How the session ended.
+    pub footer: Footer,
+}
+
+/// Check whether a line of a log file is one of the entries that the intermediary adds alongside
+/// the messages and responses, such as the footer.
+///
+/// A [`LogTimeout`] or [`LogCrash`] is deliberately not a note, because it takes the place of the
+/// response that it interrupts; [`Trim`](crate::log::Trim) and [`Summary`](crate::log::Summary)
+/// handle those entries themselves.
+pub fn is_note(line: &str) -> bool {
+    serde_json::from_str::<LogHost>(line).is_ok()
+        || serde_json::from_str::<LogSettings>(line).is_ok()
+        || serde_json::from_str::<LogProtocol>(line).is_ok()
+        || serde_json::from_str::<LogCancelled>(line).is_ok()
+        || serde_json::from_str::<LogUnimplemented>(line).is_ok()
+        || serde_json::from_str::<LogNoise>(line).is_ok()
+        || serde_json::from_str::<LogReference>(line).is_ok()
+        || serde_json::from_str::<LogGolden>(line).is_ok()
+        || serde_json::from_str::<LogUsage>(line).is_ok()
+        || serde_json::from_str::<LogFooter>(line).is_ok()
+}
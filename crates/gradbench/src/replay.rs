@@ -0,0 +1,243 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    io::{self, BufRead, Read, Write},
+    rc::Rc,
+};
+
+use anyhow::bail;
+use serde_json::Value;
+
+use crate::{
+    arrays::decode_arrays,
+    protocol::{EvaluateResponse, Id, LogMessage, LogResponse, Message, StartResponse},
+    util::{compare_json, try_read_line, Tolerance},
+};
+
+/// The shared state of a replay, which both halves refer to.
+struct State {
+    /// The recorded messages that have not yet been sent.
+    messages: VecDeque<Message>,
+
+    /// The recorded output of each successful evaluation, by message ID.
+    recorded: HashMap<Id, Value>,
+
+    /// The output of each successful evaluation by the tool being replayed, by message ID.
+    replayed: HashMap<Id, Value>,
+
+    /// The ID of the last evaluate message sent, if its response has not yet arrived.
+    pending: Option<Id>,
+
+    /// Whether the start message has been sent but its response has not yet arrived.
+    starting: bool,
+
+    /// Whether the tool can take encoded arrays, because both the recorded eval and the tool
+    /// agreed on the `"arrays"` feature.
+    arrays: bool,
+
+    /// The tolerance for comparing numbers in outputs.
+    tolerance: Tolerance,
+
+    /// Bytes of the next message line that have not yet been read.
+    unread: VecDeque<u8>,
+
+    /// Bytes of the current response line that have been written so far.
+    unwritten: Vec<u8>,
+}
+
+impl State {
+    /// Record a line of response from the tool.
+    fn respond(&mut self, line: &[u8]) {
+        if std::mem::take(&mut self.starting) {
+            let features = serde_json::from_slice::<StartResponse>(line)
+                .ok()
+                .and_then(|response| response.features);
+            self.arrays &= features.is_some_and(|f| f.iter().any(|f| f == "arrays"));
+        }
+        if let Some(id) = self.pending.take() {
+            // Let the intermediary report any problems with the response itself.
+            if let Ok(EvaluateResponse {
+                success: true,
+                output: Some(mut output),
+                ..
+            }) = serde_json::from_slice(line)
+            {
+                if decode_arrays(&mut output).is_ok() {
+                    self.replayed.insert(id, output);
+                }
+            }
+        }
+    }
+
+    /// Analyze the output from the tool for the evaluate message with ID `of`.
+    fn analyze(&self, of: Id) -> Result<(), String> {
+        let Some(expected) = self.recorded.get(&of) else {
+            return Err(format!("no recorded output for message {of}"));
+        };
+        let Some(actual) = self.replayed.get(&of) else {
+            return Err(format!("no output for message {of}"));
+        };
+        compare_json(expected, actual, self.tolerance, &mut String::new())
+    }
+
+    /// Fill the buffer with the next message line, if any.
+    fn next(&mut self) -> io::Result<()> {
+        let Some(mut message) = self.messages.pop_front() else {
+            return Ok(());
+        };
+        match &mut message {
+            Message::Start { features, .. } => {
+                self.starting = true;
+                self.arrays = features
+                    .as_ref()
+                    .is_some_and(|f| f.iter().any(|f| f == "arrays"));
+            }
+            Message::Evaluate { id, input, .. } => {
+                self.pending = Some(*id);
+                if let (false, Some(input)) = (self.arrays, input) {
+                    decode_arrays(input).map_err(io::Error::other)?;
+                }
+            }
+            Message::Analysis {
+                of, valid, error, ..
+            } => match self.analyze(*of) {
+                Ok(()) => {
+                    *valid = true;
+                    *error = None;
+                }
+                Err(err) => {
+                    *valid = false;
+                    *error = Some(err);
+                }
+            },
+            _ => {}
+        }
+        let mut line = serde_json::to_vec(&message)?;
+        line.push(b'\n');
+        self.unread.extend(line);
+        Ok(())
+    }
+}
+
+/// An in-process stand-in for an eval, which re-sends the messages recorded in a log file.
+///
+/// Instead of forwarding the recorded analysis messages as they are, it compares the output of the
+/// tool being replayed with the recorded output, to decide whether the new output is valid.
+pub struct Replay {
+    state: Rc<RefCell<State>>,
+}
+
+impl Replay {
+    /// Read the messages and responses from a `log` file, which must not have been trimmed.
+    ///
+    /// A log from a session that passed inputs or outputs in files with the `"refs"` feature is
+    /// refused, since the eval deletes those files after each round.
+    pub fn new(log: &mut impl BufRead, tolerance: Tolerance) -> anyhow::Result<Self> {
+        let mut messages = VecDeque::new();
+        let mut recorded = HashMap::new();
+        while let Some(line) = try_read_line(log)? {
+            if let Ok(entry) = serde_json::from_str::<LogMessage>(&line) {
+                if let Message::Evaluate {
+                    id,
+                    input: None,
+                    input_ref,
+                    ..
+                } = &entry.message
+                {
+                    if input_ref.is_some() {
+                        bail!(
+                            "evaluate message {id} has its input in a file, not the log, so a \
+                             session that used the \"refs\" feature can't be replayed"
+                        );
+                    }
+                    bail!("evaluate message {id} has no input, so the log must have been trimmed");
+                }
+                // The intermediary sends these messages itself, so they aren't part of the replay.
+                if matches!(entry.message, Message::End { .. } | Message::Cancel { .. }) {
+                    continue;
+                }
+                messages.push_back(entry.message);
+            } else if let Ok(entry) = serde_json::from_str::<LogResponse<EvaluateResponse>>(&line) {
+                if let (Some(Message::Evaluate { id, .. }), Some(_)) =
+                    (messages.back(), &entry.response.output_ref)
+                {
+                    bail!(
+                        "evaluate response {id} has its output in a file, not the log, so a \
+                         session that used the \"refs\" feature can't be replayed"
+                    );
+                }
+                if let (Some(Message::Evaluate { .. }), Some(mut output)) =
+                    (messages.back(), entry.response.output)
+                {
+                    decode_arrays(&mut output)?;
+                    recorded.insert(entry.response.id, output);
+                }
+            }
+        }
+        Ok(Self {
+            state: Rc::new(RefCell::new(State {
+                messages,
+                recorded,
+                replayed: HashMap::new(),
+                pending: None,
+                starting: false,
+                arrays: false,
+                tolerance,
+                unread: VecDeque::new(),
+                unwritten: Vec::new(),
+            })),
+        })
+    }
+
+    /// Get a half that takes responses from the intermediary and a half that gives it messages,
+    /// in that order.
+    pub fn split(&self) -> (ReplayIn, ReplayOut) {
+        (
+            ReplayIn {
+                state: Rc::clone(&self.state),
+            },
+            ReplayOut {
+                state: Rc::clone(&self.state),
+            },
+        )
+    }
+}
+
+/// The half of a replay that takes responses, like the stdin of an eval.
+pub struct ReplayIn {
+    state: Rc<RefCell<State>>,
+}
+
+impl Write for ReplayIn {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        for &byte in buf {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut state.unwritten);
+                state.respond(&line);
+            } else {
+                state.unwritten.push(byte);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The half of a replay that gives messages, like the stdout of an eval.
+pub struct ReplayOut {
+    state: Rc<RefCell<State>>,
+}
+
+impl Read for ReplayOut {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = self.state.borrow_mut();
+        if state.unread.is_empty() {
+            state.next()?;
+        }
+        state.unread.read(buf)
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{anyhow, Context};
+use jsonschema::Validator;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::arrays::decode_arrays;
+
+/// The JSON Schemas for the input and output of one function, as written in a schema file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FunctionFile {
+    input: Option<Value>,
+    output: Option<Value>,
+}
+
+/// The compiled JSON Schemas for the input and output of one function, either of which may be
+/// missing.
+#[derive(Debug)]
+struct Function {
+    input: Option<Validator>,
+    output: Option<Validator>,
+}
+
+/// Which side of an evaluation to check against its schema.
+#[derive(Clone, Copy, Debug)]
+pub enum Side {
+    Input,
+    Output,
+}
+
+impl Side {
+    /// Get the name of the JSON field that holds this side of an evaluation.
+    pub fn name(self) -> &'static str {
+        match self {
+            Side::Input => "input",
+            Side::Output => "output",
+        }
+    }
+}
+
+/// The JSON Schemas for the inputs and outputs of an eval's functions.
+///
+/// These are read from a directory with a `<module>.json` file for each module, which maps the name
+/// of each function to an object with an optional `"input"` schema and an optional `"output"`
+/// schema. Functions without a schema are not checked.
+#[derive(Debug, Default)]
+pub struct Schemas {
+    functions: HashMap<(String, String), Function>,
+}
+
+impl Schemas {
+    /// Read and compile every schema file in `dir`.
+    pub fn read(dir: &Path) -> anyhow::Result<Self> {
+        let mut functions = HashMap::new();
+        let entries = fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let Some(module) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let file = fs::read_to_string(&path)
+                .with_context(|| format!("reading schema file {}", path.display()))?;
+            let module_functions: HashMap<String, FunctionFile> = serde_json::from_str(&file)
+                .with_context(|| format!("invalid schema file {}", path.display()))?;
+            for (name, function) in module_functions {
+                let compile = |schema: Option<Value>| {
+                    schema
+                        .map(|schema| {
+                            jsonschema::validator_for(&schema).map_err(|err| {
+                                anyhow!("invalid schema for {module}::{name}: {err}")
+                            })
+                        })
+                        .transpose()
+                };
+                let function = Function {
+                    input: compile(function.input)?,
+                    output: compile(function.output)?,
+                };
+                functions.insert((module.to_string(), name), function);
+            }
+        }
+        Ok(Self { functions })
+    }
+
+    /// Check one side of an evaluation of `module::function` against its schema, if it has one,
+    /// returning a description of the first violation, if any.
+    ///
+    /// Any encoded arrays in the `value` are decoded first, so that the schema only needs to
+    /// describe plain JSON.
+    pub fn check(
+        &self,
+        module: &str,
+        function: &str,
+        side: Side,
+        value: &Value,
+    ) -> Result<(), String> {
+        let validator = self
+            .functions
+            .get(&(module.to_string(), function.to_string()))
+            .and_then(|f| match side {
+                Side::Input => f.input.as_ref(),
+                Side::Output => f.output.as_ref(),
+            });
+        let Some(validator) = validator else {
+            return Ok(());
+        };
+        let mut value = value.clone();
+        decode_arrays(&mut value).map_err(|err| err.to_string())?;
+        let first = validator.iter_errors(&value).next();
+        match first {
+            None => Ok(()),
+            Some(err) => {
+                let path = err.instance_path.as_str();
+                let name = side.name();
+                Err(if path.is_empty() {
+                    format!("{name} doesn't match schema: {err}")
+                } else {
+                    format!("{name} at {path} doesn't match schema: {err}")
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+
+    use super::{Schemas, Side};
+
+    fn schemas() -> Schemas {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = json!({
+            "square": {
+                "input": { "type": "number" },
+                "output": { "type": "array", "items": { "type": "number" } },
+            },
+        });
+        fs::write(dir.path().join("hello.json"), schema.to_string()).unwrap();
+        Schemas::read(dir.path()).unwrap()
+    }
+
+    #[test]
+    fn test_schemas_match() {
+        let schemas = schemas();
+        assert_eq!(
+            schemas.check("hello", "square", Side::Input, &json!(3)),
+            Ok(()),
+        );
+        assert_eq!(
+            schemas.check("hello", "square", Side::Output, &json!([9])),
+            Ok(()),
+        );
+    }
+
+    #[test]
+    fn test_schemas_mismatch() {
+        let schemas = schemas();
+        assert_eq!(
+            schemas.check("hello", "square", Side::Output, &json!([9, "x"])),
+            Err(r#"output at /1 doesn't match schema: "x" is not of type "number""#.to_string()),
+        );
+    }
+
+    #[test]
+    fn test_schemas_missing() {
+        let schemas = schemas();
+        assert_eq!(
+            schemas.check("hello", "double", Side::Output, &json!("x")),
+            Ok(()),
+        );
+    }
+}
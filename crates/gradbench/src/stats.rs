@@ -1,20 +1,20 @@
 use std::{
     collections::BTreeMap,
     fs,
-    io::{self, BufRead, Write},
+    io::{BufRead, Write},
     path::{Path, PathBuf},
     rc::Rc,
     time::Duration,
 };
 
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, Context};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     evals_to_tools, ls,
-    protocol::{EvaluateResponse, Message},
-    util::nanos_duration,
+    protocol::{is_note, EvaluateResponse, Host, LogFooter, LogHost, Message, Usage},
+    util::{nanos_duration, open_log},
     BadOutcome,
 };
 
@@ -66,6 +66,59 @@ struct LoggedResponse<T> {
     response: T,
 }
 
+/// The resource usage of the tool, as recorded at the end of a log file by the intermediary.
+#[derive(Deserialize)]
+struct LoggedUsage {
+    /// The resource usage.
+    usage: Usage,
+}
+
+/// Get the peak memory usage of the tool and the host from a `log` file, if they were recorded.
+///
+/// Fails if the log has a host but no footer, since the host shows that it was written by a version
+/// of the intermediary that writes footers, so the session that wrote it never finished and any
+/// score from it would be misleading. Older logs never have a footer, so for them this only warns.
+fn read_notes(log: impl BufRead) -> anyhow::Result<(Option<u64>, Option<Host>)> {
+    let mut max_rss_bytes = None;
+    let mut host = None;
+    let mut footer = false;
+    for result in log.lines() {
+        let line = result?;
+        if let Ok(parsed) = serde_json::from_str::<LoggedUsage>(&line) {
+            max_rss_bytes = Some(parsed.usage.max_rss_bytes);
+        } else if let Ok(parsed) = serde_json::from_str::<LogHost>(&line) {
+            host = Some(parsed.host);
+        } else if serde_json::from_str::<LogFooter>(&line).is_ok() {
+            footer = true;
+        }
+    }
+    if !footer {
+        if host.is_some() {
+            bail!("log has no footer, so the session that wrote it did not finish");
+        }
+        println!(
+            "  warning: log has no footer, so the session that wrote it may not have finished"
+        );
+    }
+    Ok((max_rss_bytes, host))
+}
+
+/// Get the index of a `host` in the list of `hosts` with distinct hardware, adding it if it isn't
+/// there yet.
+///
+/// Only the hardware is compared, since the same machine may have another hostname or commit
+/// checked out from one log to the next without that affecting the results.
+fn host_index(hosts: &mut Vec<Host>, host: Host) -> usize {
+    let hardware = |h: &Host| (h.cpu.clone(), h.cores, h.memory_bytes, h.kernel.clone());
+    match hosts.iter().position(|h| hardware(h) == hardware(&host)) {
+        Some(index) => index,
+        None => {
+            hosts.push(host);
+            hosts.len() - 1
+        }
+    }
+}
+
 /// An average duration for a given eval, tool, and workload, plus the same for the derivative.
 #[derive(Clone, Copy, Default, Serialize)]
 struct DurationPair {
@@ -149,6 +202,9 @@ impl<R: BufRead, F: CreateFile> Scorer<R, F> for ScorerClassic {
                 message = Some(parsed.message);
                 continue;
             }
+            if is_note(&line) {
+                continue;
+            }
             let Some(msg) = message.take() else {
                 bail!("response with no preceding message");
             };
@@ -229,6 +285,9 @@ impl<R: BufRead, F: CreateFile> Scorer<R, F> for ScorerEquivFunctions {
                 message = Some(parsed.message);
                 continue;
             }
+            if is_note(&line) {
+                continue;
+            }
             let Some(msg) = message.take() else {
                 bail!("response with no preceding message");
             };
@@ -299,6 +358,14 @@ struct Col<'a> {
     /// The score of the tool for this eval, or `None` if the tool was unsuccessful.
     #[serde(skip_serializing_if = "Option::is_none")]
     score: Option<f64>,
+
+    /// The peak memory usage of the tool in bytes, if it was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory: Option<u64>,
+
+    /// The index of the host that the tool ran on in the summary, if it was recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<usize>,
 }
 
 /// A row in a table of summary data.
@@ -333,6 +400,11 @@ struct Summary<'a> {
     #[serde(flatten)]
     metadata: StatsMetadata,
 
+    /// The first host seen with each distinct hardware that the logs were recorded on, without
+    /// its Docker images.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hosts: Vec<Host>,
+
     /// The table of summary data.
     table: Vec<Row<'a>>,
 }
@@ -410,31 +482,49 @@ fn svg(output: &Path, summary: Summary) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Get the path to the log for `tool` in `dir`, preferring a compressed log if there is one.
+fn find_log(dir: &Path, tool: &str) -> PathBuf {
+    ["jsonl.zst", "jsonl.gz"]
+        .iter()
+        .map(|extension| dir.join(format!("{tool}.{extension}")))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(format!("{tool}.jsonl")))
+}
+
 /// Generate summary data and plots in `output` from logs in `input`.
 pub fn generate(input: PathBuf, output: PathBuf, metadata: StatsMetadata) -> anyhow::Result<()> {
     fs::create_dir_all(&output)?;
     let mut evals = ls("evals")?;
     evals.sort();
     let mut table = Vec::new();
+    let mut hosts = Vec::new();
     let map = evals_to_tools(evals)?;
     for (eval, supported) in &map {
         println!("{}", eval);
         let mut row = Vec::new();
         let mut scorer = scorer(eval);
         for (tool, &outcome) in supported {
-            let score = match outcome {
-                Some(BadOutcome::Undefined) => None,
+            let (score, memory, host) = match outcome {
+                Some(BadOutcome::Undefined) => (None, None, None),
                 _ => {
-                    let path = input.join(format!("{eval}/{tool}.jsonl"));
+                    let path = find_log(&input.join(eval), tool);
                     println!("  {}", path.display());
-                    let reader = io::BufReader::new(fs::File::open(&path)?);
+                    // Check for a footer first, to refuse incomplete logs before scoring them.
+                    let (memory, host) = read_notes(open_log(&path)?)
+                        .with_context(|| format!("failed to read {}", path.display()))?;
+                    // Each tool runs different images, so leave them out of the summary.
+                    let host = host.map(|host| Host {
+                        images: BTreeMap::new(),
+                        ..host
+                    });
+                    let host = host.map(|host| host_index(&mut hosts, host));
                     // Always run the `score` method, to gather fine-grained data.
-                    let score = scorer.score(tool, reader)?;
+                    let score = scorer.score(tool, open_log(&path)?)?;
                     // Only give the tool an overall score if it successfully completed the eval.
                     if outcome.is_none() {
-                        Some(score)
+                        (Some(score), memory, host)
                     } else {
-                        None
+                        (None, memory, host)
                     }
                 }
             };
@@ -442,14 +532,23 @@ pub fn generate(input: PathBuf, output: PathBuf, metadata: StatsMetadata) -> any
                 tool,
                 outcome,
                 score,
+                memory,
+                host,
             });
         }
         scorer.finish(output.join("evals").join(eval))?;
         table.push(Row { eval, tools: row });
     }
+    if hosts.len() > 1 {
+        println!(
+            "warning: the logs are from {} different hosts, listed in summary.json",
+            hosts.len(),
+        );
+    }
     let summary = Summary {
         version: 1,
         metadata,
+        hosts,
         table,
     };
     let file = fs::File::create(output.join("summary.json"))?;
@@ -457,3 +556,47 @@ pub fn generate(input: PathBuf, output: PathBuf, metadata: StatsMetadata) -> any
     svg(&output, summary)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::Host;
+
+    use super::{host_index, read_notes};
+
+    const MESSAGE: &str = r#"{ "elapsed": { "nanoseconds": 0 }, "message": {"id": 0, "kind": "start"} }
+{ "elapsed": { "nanoseconds": 1 }, "response": {"id": 0} }
+"#;
+
+    const HOST: &str = r#"{ "elapsed": { "nanoseconds": 0 }, "host": {"gradbench": "0.0.0"} }
+"#;
+
+    #[test]
+    fn test_read_notes_old_log() {
+        assert!(read_notes(MESSAGE.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_read_notes_incomplete() {
+        let log = format!("{HOST}{MESSAGE}");
+        assert!(read_notes(log.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_host_index_hardware() {
+        let host = |hostname: &str, commit: &str, cores| Host {
+            hostname: Some(hostname.to_string()),
+            cpu: Some("AMD EPYC".to_string()),
+            cores: Some(cores),
+            kernel: Some("Linux 6.8".to_string()),
+            memory_bytes: Some(1 << 34),
+            gradbench: "0.0.0".to_string(),
+            commit: Some(commit.to_string()),
+            images: Default::default(),
+        };
+        let mut hosts = Vec::new();
+        assert_eq!(host_index(&mut hosts, host("a", "abc", 8)), 0);
+        assert_eq!(host_index(&mut hosts, host("b", "def", 8)), 0);
+        assert_eq!(host_index(&mut hosts, host("a", "abc", 16)), 1);
+        assert_eq!(hosts.len(), 2);
+    }
+}
@@ -1,15 +1,24 @@
 use std::{
-    collections::HashMap,
-    fs, io, iter,
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    fs,
+    io::{self, BufRead},
+    iter,
     mem::take,
+    num::NonZeroUsize,
     ops::DerefMut,
     path::Path,
     process::Command,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
     time::Duration,
 };
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
+
+use crate::protocol::{Nanoseconds, Settings, Usage};
 
 pub trait InOut<T> {
     fn run(self, input: impl io::Read, output: impl io::Write) -> T;
@@ -21,22 +30,148 @@ fn run_out(
     output: Option<&Path>,
 ) -> anyhow::Result<()> {
     match output {
-        Some(path) => f.run(input, fs::File::create(path)?),
+        Some(path) => {
+            let mut log = create_log(path)?;
+            f.run(input, &mut log)?;
+            log.finish()
+                .with_context(|| format!("finishing {}", path.display()))
+        }
         None => f.run(input, io::stdout()),
     }
 }
 
+/// Run `f` on a log from a file or stdin, decompressing it if need be, and write its output to a
+/// file or stdout, compressing it if the file extension says to.
 pub fn run_in_out(
     f: impl InOut<anyhow::Result<()>>,
     input: Option<&Path>,
     output: Option<&Path>,
 ) -> anyhow::Result<()> {
     match input {
-        Some(path) => run_out(f, fs::File::open(path)?, output),
-        None => run_out(f, io::stdin(), output),
+        Some(path) => run_out(f, open_log(path)?, output),
+        None => run_out(f, decompress(io::stdin(), None)?, output),
+    }
+}
+
+/// A compression format for log files.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Codec {
+    /// Gzip, for files with the `.gz` extension.
+    Gzip,
+
+    /// Zstandard, for files with the `.zst` extension.
+    Zstd,
+}
+
+impl Codec {
+    /// Get the codec for a file from its extension, if it is compressed.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" => Some(Codec::Gzip),
+            "zst" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Get the codec for a stream from its first bytes, if it is compressed.
+    fn from_magic(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Codec::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Codec::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decompress a stream with the given `codec`, or with the codec given by its first bytes if none.
+pub fn decompress<'a>(
+    reader: impl io::Read + 'a,
+    codec: Option<Codec>,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut reader = io::BufReader::new(reader);
+    let codec = match codec {
+        Some(codec) => Some(codec),
+        None => Codec::from_magic(reader.fill_buf()?),
+    };
+    Ok(match codec {
+        Some(Codec::Gzip) => Box::new(io::BufReader::new(flate2::bufread::MultiGzDecoder::new(
+            reader,
+        ))),
+        Some(Codec::Zstd) => Box::new(io::BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+/// Open a log file, decompressing it according to its extension or else its first bytes.
+pub fn open_log(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    decompress(fs::File::open(path)?, Codec::from_extension(path))
+}
+
+/// A stream that may be compressed, which is only complete once it is finished.
+pub enum Compressed<W: io::Write> {
+    /// Not compressed.
+    None(W),
+
+    /// Compressed with gzip.
+    Gzip(flate2::write::GzEncoder<W>),
+
+    /// Compressed with Zstandard.
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: io::Write> Compressed<W> {
+    /// Write the end of the compressed stream and flush it, returning any error, which would be
+    /// lost if the stream were just dropped.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Compressed::None(mut writer) => writer.flush(),
+            Compressed::Gzip(encoder) => encoder.finish()?.flush(),
+            Compressed::Zstd(encoder) => encoder.finish()?.flush(),
+        }
     }
 }
 
+impl<W: io::Write> io::Write for Compressed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressed::None(writer) => writer.write(buf),
+            Compressed::Gzip(encoder) => encoder.write(buf),
+            Compressed::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressed::None(writer) => writer.flush(),
+            Compressed::Gzip(encoder) => encoder.flush(),
+            Compressed::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Compress a stream with the given `codec`, if any.
+///
+/// A compressed stream is only complete once the returned writer is finished.
+pub fn compress<W: io::Write>(writer: W, codec: Option<Codec>) -> io::Result<Compressed<W>> {
+    Ok(match codec {
+        Some(Codec::Gzip) => Compressed::Gzip(flate2::write::GzEncoder::new(
+            writer,
+            flate2::Compression::default(),
+        )),
+        Some(Codec::Zstd) => Compressed::Zstd(zstd::Encoder::new(writer, 0)?),
+        None => Compressed::None(writer),
+    })
+}
+
+/// Create a log file, compressing it if its extension names a codec.
+///
+/// A compressed file is only complete once the returned writer is finished.
+pub fn create_log(path: &Path) -> io::Result<Compressed<fs::File>> {
+    compress(fs::File::create(path)?, Codec::from_extension(path))
+}
+
 const BILLION: u128 = 1_000_000_000;
 
 pub fn nanos_duration(nanoseconds: u128) -> anyhow::Result<Duration> {
@@ -55,6 +190,118 @@ pub fn try_read_line(file: &mut impl io::BufRead) -> anyhow::Result<Option<Strin
     }
 }
 
+/// How far a number may be from an expected number while still being considered equal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tolerance {
+    /// The allowed absolute difference.
+    pub absolute: f64,
+
+    /// The allowed difference, relative to the magnitude of the expected number.
+    pub relative: f64,
+}
+
+/// Compare an `actual` JSON value to an `expected` one, returning an error describing the first
+/// difference found.
+///
+/// Numbers match if they differ by at most the absolute tolerance plus the relative tolerance times
+/// the magnitude of the expected number. The `path` is used to say where in the value a difference
+/// is.
+pub fn compare_json(
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+    tolerance: Tolerance,
+    path: &mut String,
+) -> Result<(), String> {
+    compare_json_by(expected, actual, &|_| tolerance, path)
+}
+
+/// Like [`compare_json`], but with a tolerance for each number that depends on its `path`.
+pub fn compare_json_by(
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+    tolerance: &dyn Fn(&str) -> Tolerance,
+    path: &mut String,
+) -> Result<(), String> {
+    use serde_json::Value;
+    match (expected, actual) {
+        (Value::Number(x), Value::Number(y)) => {
+            let (x, y) = (x.as_f64().unwrap(), y.as_f64().unwrap());
+            let tolerance = tolerance(path);
+            if (x - y).abs() <= tolerance.absolute + tolerance.relative * x.abs() {
+                return Ok(());
+            }
+        }
+        (Value::Array(xs), Value::Array(ys)) if xs.len() == ys.len() => {
+            for (i, (x, y)) in xs.iter().zip(ys).enumerate() {
+                let len = path.len();
+                path.push_str(&format!("[{i}]"));
+                compare_json_by(x, y, tolerance, path)?;
+                path.truncate(len);
+            }
+            return Ok(());
+        }
+        (Value::Object(xs), Value::Object(ys))
+            if xs.len() == ys.len() && xs.keys().all(|key| ys.contains_key(key)) =>
+        {
+            for (key, x) in xs {
+                let len = path.len();
+                path.push_str(&format!(".{key}"));
+                compare_json_by(x, &ys[key], tolerance, path)?;
+                path.truncate(len);
+            }
+            return Ok(());
+        }
+        (Value::Array(xs), Value::Array(ys)) => {
+            return Err(format!(
+                "output{path} differs: expected {} elements, got {}",
+                xs.len(),
+                ys.len(),
+            ));
+        }
+        (Value::Object(xs), Value::Object(ys)) => {
+            let missing: Vec<&str> = (xs.keys())
+                .filter(|key| !ys.contains_key(*key))
+                .map(String::as_str)
+                .collect();
+            let extra: Vec<&str> = (ys.keys())
+                .filter(|key| !xs.contains_key(*key))
+                .map(String::as_str)
+                .collect();
+            let mut problems = Vec::new();
+            if !missing.is_empty() {
+                problems.push(format!("missing keys {}", brief(&missing.join(", "))));
+            }
+            if !extra.is_empty() {
+                problems.push(format!("extra keys {}", brief(&extra.join(", "))));
+            }
+            return Err(format!("output{path} differs: {}", problems.join(", ")));
+        }
+        _ => {
+            if expected == actual {
+                return Ok(());
+            }
+        }
+    }
+    Err(format!(
+        "output{path} differs: expected {}, got {}",
+        brief(&expected.to_string()),
+        brief(&actual.to_string()),
+    ))
+}
+
+/// The longest value, in characters, to quote whole in a message saying how two values differ.
+const BRIEF_LEN: usize = 60;
+
+/// Shorten `s` to at most [`BRIEF_LEN`] characters, replacing what's cut with an ellipsis.
+fn brief(s: &str) -> String {
+    if s.chars().count() <= BRIEF_LEN {
+        return s.to_string();
+    }
+    let mut short: String = s.chars().take(BRIEF_LEN - 3).collect();
+    short.push_str("...");
+    short
+}
+
 /// Return an 11-character human-readable string for the given number of nanoseconds.
 pub fn nanostring(nanoseconds: u128) -> String {
     let ms = nanoseconds / 1_000_000;
@@ -71,13 +318,64 @@ pub fn nanostring(nanoseconds: u128) -> String {
     }
 }
 
-pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+/// Return a short human-readable string for an estimated duration, rounded to the nearest second.
+pub fn etastring(duration: Duration) -> String {
+    let sec = (duration.as_millis() + 500) / 1000;
+    let (hr, min, sec) = (sec / 3600, sec / 60 % 60, sec % 60);
+    if hr > 0 {
+        format!("{hr}h{min:02}m")
+    } else if min > 0 {
+        format!("{min}m{sec:02}s")
+    } else {
+        format!("{sec}s")
+    }
+}
+
+pub fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
     match mutex.lock() {
         Ok(guard) => guard,
         Err(poison_error) => poison_error.into_inner(),
     }
 }
 
+/// Where the output of a session goes, which can be shared with the threads that echo the stderr
+/// of its eval and tools.
+///
+/// Each call to `write_all` or `write_fmt` holds the lock throughout, so a line written with one of
+/// them is never split by another thread's output.
+#[derive(Clone)]
+pub struct Console(Arc<Mutex<dyn io::Write + Send>>);
+
+impl Console {
+    /// Write to `inner`, such as a buffer that is printed later.
+    pub fn new(inner: Arc<Mutex<dyn io::Write + Send>>) -> Self {
+        Self(inner)
+    }
+
+    /// Write to stdout.
+    pub fn stdout() -> Self {
+        Self(Arc::new(Mutex::new(io::stdout())))
+    }
+}
+
+impl io::Write for Console {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        lock(&self.0).write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        lock(&self.0).write_all(buf)
+    }
+
+    fn write_fmt(&mut self, args: std::fmt::Arguments<'_>) -> io::Result<()> {
+        lock(&self.0).write_fmt(args)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        lock(&self.0).flush()
+    }
+}
+
 pub fn stringify_cmd(cmd: &Command) -> anyhow::Result<Vec<&str>> {
     iter::once(cmd.get_program())
         .chain(cmd.get_args())
@@ -92,11 +390,285 @@ pub fn shlex_cmd(cmd: &Command) -> anyhow::Result<String> {
     Ok(shlex::try_join(stringify_cmd(cmd)?)?)
 }
 
+/// Parse a list of CPUs like `0-3,6` into their indices.
+pub fn parse_cpus(list: &str) -> anyhow::Result<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in list.split(',') {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let parse = |cpu: &str| {
+            cpu.trim()
+                .parse::<usize>()
+                .with_context(|| format!("invalid CPU {cpu:?} in list {list:?}"))
+        };
+        let (first, last) = (parse(first)?, parse(last)?);
+        if first > last {
+            bail!("invalid CPU range {part:?} in list {list:?}");
+        }
+        cpus.extend(first..=last);
+    }
+    Ok(cpus)
+}
+
+/// Format a list of CPU indices like `0-3,6`, the inverse of [`parse_cpus`].
+fn format_cpus(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if cpu == *last + 1 => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+    let parts: Vec<String> = ranges
+        .into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect();
+    parts.join(",")
+}
+
+/// Split a list of CPUs like `0-3,6` into the given number of disjoint lists, as evenly as
+/// possible, so that concurrent sessions can each have their own CPUs.
+pub fn partition_cpus(list: &str, parts: NonZeroUsize) -> anyhow::Result<Vec<String>> {
+    let cpus = parse_cpus(list)?;
+    let parts = parts.get();
+    if cpus.len() < parts {
+        bail!(
+            "can't split the {} CPUs in {list:?} into {parts} groups",
+            cpus.len()
+        );
+    }
+    let (size, extra) = (cpus.len() / parts, cpus.len() % parts);
+    let mut rest = &cpus[..];
+    Ok((0..parts)
+        .map(|i| {
+            let (group, after) = rest.split_at(size + usize::from(i < extra));
+            rest = after;
+            format_cpus(group)
+        })
+        .collect())
+}
+
+/// Get the settings for tools from the `--cpus` and `--threads` options, if either was given.
+pub fn tool_settings(cpus: Option<String>, threads: Option<NonZeroUsize>) -> Option<Settings> {
+    if cpus.is_none() && threads.is_none() {
+        return None;
+    }
+    let mut env = BTreeMap::new();
+    if let Some(threads) = threads {
+        for name in [
+            "OMP_NUM_THREADS",
+            "MKL_NUM_THREADS",
+            "OPENBLAS_NUM_THREADS",
+            "JULIA_NUM_THREADS",
+            "TF_NUM_INTRAOP_THREADS",
+            "TF_NUM_INTEROP_THREADS",
+        ] {
+            env.insert(name.to_string(), threads.to_string());
+        }
+        // XLA has no flag for a thread count, but it can at least be kept to one thread.
+        if threads.get() == 1 {
+            let flag = "--xla_cpu_multi_thread_eigen=false";
+            env.insert("XLA_FLAGS".to_string(), flag.to_string());
+        }
+    }
+    Some(Settings { cpus, env })
+}
+
+/// Apply `settings` to a tool command, so this must be called before configuring its stdio.
+///
+/// A `docker run` command is replaced by one with the settings as Docker options, since neither
+/// the CPU affinity nor the environment of the Docker client reach the container. So a container
+/// gets exactly the `XLA_FLAGS` in the settings, whereas any other tool keeps the flags that the
+/// user set, with those in the settings appended.
+pub fn constrain(cmd: &mut Command, settings: &Settings) -> anyhow::Result<()> {
+    let cpus = settings.cpus.as_deref().map(parse_cpus).transpose()?;
+    let mut options = Vec::new();
+    if let Some(cpus) = &settings.cpus {
+        options.extend(["--cpuset-cpus".into(), cpus.into()]);
+    }
+    for (name, value) in &settings.env {
+        options.extend(["--env".into(), format!("{name}={value}").into()]);
+    }
+    if docker_run_options(cmd, options) {
+        return Ok(());
+    }
+    let mut env = settings.env.clone();
+    if let (Some(flags), Ok(user)) = (env.get_mut("XLA_FLAGS"), std::env::var("XLA_FLAGS")) {
+        if !user.trim().is_empty() {
+            *flags = format!("{} {flags}", user.trim());
+        }
+    }
+    cmd.envs(&env);
+    if let Some(cpus) = cpus {
+        pin(cmd, &cpus)?;
+    }
+    Ok(())
+}
+
+/// Make a new command with the same program, arguments, environment and working directory as
+/// `cmd`, but none of its other configuration, such as its stdio, so it can be set up differently.
+pub fn copy_command(cmd: &Command) -> Command {
+    let mut copy = Command::new(cmd.get_program());
+    copy.args(cmd.get_args());
+    for (name, value) in cmd.get_envs() {
+        match value {
+            Some(value) => copy.env(name, value),
+            None => copy.env_remove(name),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        copy.current_dir(dir);
+    }
+    copy
+}
+
+/// The environment variable that tells evals and tools where the scratch directory is.
+pub const SCRATCH_VAR: &str = "GRADBENCH_SCRATCH";
+
+/// Give an eval or tool command access to the scratch directory at the absolute path `dir`, so
+/// this must be called before configuring its stdio.
+///
+/// A `docker run` command is replaced by one that mounts the directory at the same path in the
+/// container.
+pub fn share(cmd: &mut Command, dir: &Path) {
+    let mut volume = dir.as_os_str().to_owned();
+    volume.push(":");
+    volume.push(dir);
+    let mut env = OsString::from(format!("{SCRATCH_VAR}="));
+    env.push(dir);
+    if !docker_run_options(cmd, vec!["--volume".into(), volume, "--env".into(), env]) {
+        cmd.env(SCRATCH_VAR, dir);
+    }
+}
+
+/// Check whether `cmd` is a `docker run` command, whose process is only the Docker client rather
+/// than what runs in the container.
+pub fn is_docker_run(cmd: &Command) -> bool {
+    cmd.get_program() == "docker" && cmd.get_args().next().is_some_and(|arg| arg == "run")
+}
+
+/// Replace `cmd` with one that has the given `options` right after `run`, if it is a `docker run`
+/// command, returning whether it was.
+fn docker_run_options(cmd: &mut Command, options: Vec<OsString>) -> bool {
+    if !is_docker_run(cmd) {
+        return false;
+    }
+    let mut docker = Command::new("docker");
+    docker.arg("run").args(options).args(cmd.get_args().skip(1));
+    for (name, value) in cmd.get_envs() {
+        match value {
+            Some(value) => docker.env(name, value),
+            None => docker.env_remove(name),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        docker.current_dir(dir);
+    }
+    *cmd = docker;
+    true
+}
+
+/// Make a `docker run` command write the ID of its container to a file, returning the path of
+/// that file, which is deleted once dropped; returns `None` for any other command.
+pub fn container_id_file(cmd: &mut Command) -> io::Result<Option<Arc<tempfile::TempPath>>> {
+    if !is_docker_run(cmd) {
+        return Ok(None);
+    }
+    let path = tempfile::Builder::new()
+        .prefix("gradbench-")
+        .suffix(".cid")
+        .tempfile()?
+        .into_temp_path();
+    // Docker refuses to write to a file that already exists, so only keep its name.
+    fs::remove_file(&path)?;
+    docker_run_options(cmd, vec!["--cidfile".into(), path.as_os_str().into()]);
+    Ok(Some(Arc::new(path)))
+}
+
+/// Read the resource usage of the container whose ID was written to `cidfile`, from its cgroup,
+/// which only exists until the container exits.
+///
+/// Returns `None` unless the container runs on this machine with cgroup v2, which doesn't count
+/// context switches. Its peak memory includes the page cache of the container, unlike the resident
+/// set size of a process.
+#[cfg(target_os = "linux")]
+pub fn container_usage(cidfile: &Path) -> Option<Usage> {
+    let id = fs::read_to_string(cidfile).ok()?;
+    let id = id.trim();
+    let dir = [
+        format!("/sys/fs/cgroup/system.slice/docker-{id}.scope"),
+        format!("/sys/fs/cgroup/docker/{id}"),
+    ]
+    .into_iter()
+    .map(std::path::PathBuf::from)
+    .find(|dir| dir.join("memory.peak").exists())?;
+    let max_rss_bytes = fs::read_to_string(dir.join("memory.peak"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let stat = fs::read_to_string(dir.join("cpu.stat")).ok()?;
+    let usec = |key: &str| {
+        let value = stat
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '));
+        let usec: u128 = value?.trim().parse().ok()?;
+        Some(Nanoseconds {
+            nanoseconds: usec * 1000,
+        })
+    };
+    Some(Usage {
+        max_rss_bytes,
+        user: usec("user_usec")?,
+        system: usec("system_usec")?,
+        voluntary_context_switches: None,
+        involuntary_context_switches: None,
+    })
+}
+
+/// Read the resource usage of a container; only implemented on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn container_usage(_: &Path) -> Option<Usage> {
+    None
+}
+
+/// Make the process spawned by `cmd` run only on the given `cpus`, as will all its descendants.
+#[cfg(target_os = "linux")]
+fn pin(cmd: &mut Command, cpus: &[usize]) -> anyhow::Result<()> {
+    use nix::{
+        sched::{sched_setaffinity, CpuSet},
+        unistd::Pid,
+    };
+    use std::os::unix::process::CommandExt;
+
+    let mut set = CpuSet::new();
+    for &cpu in cpus {
+        set.set(cpu)
+            .with_context(|| format!("CPU {cpu} is out of range"))?;
+    }
+    // SAFETY: the closure only makes one system call, which is fine between `fork` and `exec`.
+    unsafe {
+        cmd.pre_exec(move || Ok(sched_setaffinity(Pid::from_raw(0), &set)?));
+    }
+    Ok(())
+}
+
+/// Make the process spawned by `cmd` run only on the given `cpus`, as will all its descendants.
+#[cfg(not(target_os = "linux"))]
+fn pin(_: &mut Command, _: &[usize]) -> anyhow::Result<()> {
+    bail!("pinning to CPUs is only implemented on Linux, or for tools run with `docker run`")
+}
+
 type CtrlCHandlers = HashMap<usize, Box<dyn FnOnce() + Send>>;
 
 pub struct CtrlC {
     handlers: Arc<Mutex<CtrlCHandlers>>,
-    next_key: usize,
+    next_key: AtomicUsize,
 }
 
 impl CtrlC {
@@ -104,7 +676,7 @@ impl CtrlC {
         let handlers = Arc::new(Mutex::new(HashMap::new()));
         let obj = Self {
             handlers: Arc::clone(&handlers),
-            next_key: 0,
+            next_key: AtomicUsize::new(0),
         };
         ctrlc::set_handler(move || {
             let map = {
@@ -118,10 +690,11 @@ impl CtrlC {
         Ok(obj)
     }
 
-    pub fn handle(&mut self, f: Box<dyn FnOnce() + Send>) -> CtrlCHandler {
-        let key = self.next_key;
+    /// Run `f` on Ctrl-C, until the returned handler is dropped. This can be called from several
+    /// threads at once, each of which gets its own registration.
+    pub fn handle(&self, f: Box<dyn FnOnce() + Send>) -> CtrlCHandler<'_> {
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed);
         lock(&self.handlers).insert(key, f);
-        self.next_key += 1;
         CtrlCHandler { ctrl_c: self, key }
     }
 }
@@ -139,8 +712,49 @@ impl Drop for CtrlCHandler<'_> {
 
 #[cfg(test)]
 mod tests {
+    use std::io::{Read, Write};
+
+    use serde_json::json;
+
     use super::*;
 
+    #[test]
+    fn test_compressed_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = "{ \"elapsed\": { \"nanoseconds\": 0 }, \"message\": {} }\n".repeat(100);
+        for name in ["log.jsonl", "log.jsonl.gz", "log.jsonl.zst"] {
+            let path = dir.path().join(name);
+            let mut file = create_log(&path).unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+            file.finish().unwrap();
+            let mut read = String::new();
+            open_log(&path).unwrap().read_to_string(&mut read).unwrap();
+            assert_eq!(read, text, "{name}");
+            // Without the extension, the codec should be detected from the first bytes.
+            let renamed = dir.path().join(format!("{name}.log"));
+            fs::rename(&path, &renamed).unwrap();
+            let mut read = String::new();
+            open_log(&renamed)
+                .unwrap()
+                .read_to_string(&mut read)
+                .unwrap();
+            assert_eq!(read, text, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_partition_cpus() {
+        let parts = NonZeroUsize::new(3).unwrap();
+        let groups = partition_cpus("0-3,6,8-9", parts).unwrap();
+        assert_eq!(groups, ["0-2", "3,6", "8-9"]);
+    }
+
+    #[test]
+    fn test_partition_cpus_too_few() {
+        let parts = NonZeroUsize::new(3).unwrap();
+        assert!(partition_cpus("0-1", parts).is_err());
+    }
+
     #[test]
     fn test_nanos_duration_max() {
         let nanos = u128::from(u64::MAX) * BILLION + (BILLION - 1);
@@ -203,4 +817,133 @@ mod tests {
     fn test_nanostring_1_hour() {
         nanostring_test("     > 1 hr", Duration::from_secs(3600));
     }
+
+    #[test]
+    fn test_compare_json_tolerance() {
+        let tolerance = Tolerance {
+            absolute: 1e-6,
+            relative: 1e-6,
+        };
+        let expected = json!({ "x": [1.0, 1000.0], "y": "z" });
+        let close = json!({ "x": [1.0000001, 1000.0001], "y": "z" });
+        assert_eq!(
+            compare_json(&expected, &close, tolerance, &mut String::new()),
+            Ok(()),
+        );
+        let far = json!({ "x": [1.0, 1000.01], "y": "z" });
+        assert_eq!(
+            compare_json(&expected, &far, tolerance, &mut String::new()),
+            Err(String::from(
+                "output.x[1] differs: expected 1000.0, got 1000.01"
+            )),
+        );
+    }
+
+    #[test]
+    fn test_compare_json_shape() {
+        let expected = json!([1, 2, 3]);
+        let actual = json!([1, 2]);
+        assert_eq!(
+            compare_json(&expected, &actual, Tolerance::default(), &mut String::new()),
+            Err(String::from("output differs: expected 3 elements, got 2")),
+        );
+        let expected = json!({ "x": { "a": 1, "b": 2 } });
+        let actual = json!({ "x": { "a": 1, "c": 2 } });
+        assert_eq!(
+            compare_json(&expected, &actual, Tolerance::default(), &mut String::new()),
+            Err(String::from(
+                "output.x differs: missing keys b, extra keys c"
+            )),
+        );
+        let expected = json!("a".repeat(100));
+        let actual = json!(1);
+        assert_eq!(
+            compare_json(&expected, &actual, Tolerance::default(), &mut String::new()),
+            Err(format!(
+                "output differs: expected \"{}..., got 1",
+                "a".repeat(56)
+            )),
+        );
+    }
+
+    #[test]
+    fn test_parse_cpus() {
+        assert_eq!(parse_cpus("0-3,6").unwrap(), vec![0, 1, 2, 3, 6]);
+        assert_eq!(parse_cpus("5").unwrap(), vec![5]);
+        assert!(parse_cpus("3-1").is_err());
+        assert!(parse_cpus("0,,1").is_err());
+    }
+
+    #[test]
+    fn test_constrain_docker() {
+        let settings = tool_settings(Some("0-1".to_string()), NonZeroUsize::new(2)).unwrap();
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "--rm", "--interactive", "ghcr.io/gradbench/tool-foo"]);
+        constrain(&mut cmd, &settings).unwrap();
+        assert_eq!(
+            stringify_cmd(&cmd).unwrap(),
+            [
+                "docker",
+                "run",
+                "--cpuset-cpus",
+                "0-1",
+                "--env",
+                "JULIA_NUM_THREADS=2",
+                "--env",
+                "MKL_NUM_THREADS=2",
+                "--env",
+                "OMP_NUM_THREADS=2",
+                "--env",
+                "OPENBLAS_NUM_THREADS=2",
+                "--env",
+                "TF_NUM_INTEROP_THREADS=2",
+                "--env",
+                "TF_NUM_INTRAOP_THREADS=2",
+                "--rm",
+                "--interactive",
+                "ghcr.io/gradbench/tool-foo",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_constrain_env() {
+        let settings = tool_settings(None, NonZeroUsize::new(1)).unwrap();
+        let mut cmd = Command::new("python3");
+        constrain(&mut cmd, &settings).unwrap();
+        let env: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(env.len(), 7);
+        assert!(env.contains(&("OMP_NUM_THREADS".as_ref(), Some("1".as_ref()))));
+        let flag = "--xla_cpu_multi_thread_eigen=false";
+        assert_eq!(settings.env["XLA_FLAGS"], flag);
+        let xla = cmd.get_envs().find(|&(name, _)| name == "XLA_FLAGS");
+        assert!(xla.unwrap().1.unwrap().to_str().unwrap().ends_with(flag));
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "--rm", "--interactive", "ghcr.io/gradbench/tool-jax"]);
+        constrain(&mut cmd, &settings).unwrap();
+        let args = stringify_cmd(&cmd).unwrap();
+        assert!(args.contains(&format!("XLA_FLAGS={flag}").as_str()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_share_docker() {
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "--rm", "--interactive", "ghcr.io/gradbench/eval-ba"]);
+        share(&mut cmd, Path::new("/tmp/scratch"));
+        assert_eq!(
+            stringify_cmd(&cmd).unwrap(),
+            [
+                "docker",
+                "run",
+                "--volume",
+                "/tmp/scratch:/tmp/scratch",
+                "--env",
+                "GRADBENCH_SCRATCH=/tmp/scratch",
+                "--rm",
+                "--interactive",
+                "ghcr.io/gradbench/eval-ba",
+            ],
+        );
+    }
 }
@@ -53,7 +53,8 @@ const ScoredRow = (row: Row) => {
           {(outcome === "error" ||
             outcome == "failure" ||
             outcome == "interrupt" ||
-            outcome == "invalid") && (
+            outcome == "invalid" ||
+            outcome == "malformed") && (
             <svg
               xmlns="http://www.w3.org/2000/svg"
               height="20px"
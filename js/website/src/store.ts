@@ -1,6 +1,9 @@
 export interface Cell {
   tool: string;
   outcome?:
+    | "malformed"
+    | "crash"
+    | "violation"
     | "interrupt"
     | "timeout"
     | "invalid"
@@ -8,6 +11,7 @@ export interface Cell {
     | "undefined"
     | "error";
   score?: number;
+  memory?: number;
   status?: "unimplemented" | "incorrect" | "correct";
 }
 
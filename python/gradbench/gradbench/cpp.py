@@ -1,5 +1,6 @@
 import argparse
 import json
+import os
 import subprocess
 import sys
 import tempfile
@@ -46,28 +47,43 @@ def define(*, args: argparse.Namespace, module: str) -> Any:
         return {"success": True}
 
 
+def evaluate_file(*, tool: str, module: str, function: str, path: str) -> Any:
+    return evaluate_completed_process(
+        subprocess.run(
+            [f"tools/{tool}/bin/{module}", path, function],
+            text=True,
+            capture_output=True,
+        )
+    )
+
+
 def evaluate(*, tool: str, module: str, function: str, input: Any) -> Any:
     with tempfile.NamedTemporaryFile("w") as tmp:
         json.dump(input, tmp)
         tmp.flush()
-        return evaluate_completed_process(
-            subprocess.run(
-                [f"tools/{tool}/bin/{module}", tmp.name, function],
-                text=True,
-                capture_output=True,
-            )
-        )
+        return evaluate_file(tool=tool, module=module, function=function, path=tmp.name)
 
 
 def run(args: argparse.Namespace) -> None:
+    scratch = os.environ.get("GRADBENCH_SCRATCH")
     for line in sys.stdin:
         message = json.loads(line)
         response = {"id": message["id"]}
         match message["kind"]:
             case "start":
                 response["tool"] = args.tool
+                if scratch is not None and "refs" in message.get("features", []):
+                    # The input files are already what the executables expect.
+                    response["features"] = ["refs"]
             case "define":
                 response |= define(args=args, module=message["module"])
+            case "evaluate" if "input_ref" in message:
+                response |= evaluate_file(
+                    tool=args.tool,
+                    module=message["module"],
+                    function=message["function"],
+                    path=os.path.join(scratch, message["input_ref"]),
+                )
             case "evaluate":
                 response |= evaluate(
                     tool=args.tool,
@@ -1,12 +1,58 @@
+import base64
 import json
+import os
 import sys
 import traceback
 from typing import Any, Callable, Optional
 
+import numpy as np
 from pydantic import BaseModel
 
 from gradbench.comparison import compare_json_objects
 
+# The element types of encoded arrays, as little-endian NumPy dtypes.
+ARRAY_DTYPES = {"f64": "<f8", "f32": "<f4", "i64": "<i8", "i32": "<i4"}
+
+# Arrays with fewer elements than this stay as plain JSON, which is easier to
+# read in logs and costs little to parse.
+ARRAY_THRESHOLD = 256
+
+
+def encode_arrays(value: Any) -> Any:
+    """Replace large nested lists of floats with encoded arrays."""
+    if isinstance(value, list):
+        try:
+            array = np.asarray(value)
+        except ValueError:
+            array = None
+        if (
+            array is not None
+            and array.dtype == np.float64
+            and array.size >= ARRAY_THRESHOLD
+        ):
+            return {
+                "$array": "f64",
+                "shape": list(array.shape),
+                "base64": base64.b64encode(array.astype("<f8").tobytes()).decode(),
+            }
+        return [encode_arrays(x) for x in value]
+    if isinstance(value, dict):
+        return {k: encode_arrays(v) for k, v in value.items()}
+    return value
+
+
+def decode_arrays(value: Any) -> Any:
+    """Replace encoded arrays with nested lists of numbers."""
+    if isinstance(value, list):
+        return [decode_arrays(x) for x in value]
+    if isinstance(value, dict):
+        if "$array" in value:
+            data = base64.b64decode(value["base64"])
+            array = np.frombuffer(data, dtype=ARRAY_DTYPES[value["$array"]])
+            return array.reshape(value["shape"]).tolist()
+        return {k: decode_arrays(v) for k, v in value.items()}
+    return value
+
 
 class Timing(BaseModel):
     name: str
@@ -17,6 +63,7 @@ class StartResponse(BaseModel):
     id: int
     tool: Optional[str] = None
     config: Optional[Any] = None
+    features: Optional[list[str]] = None
 
 
 class DefineResponse(BaseModel):
@@ -30,6 +77,7 @@ class EvaluateResponse(BaseModel):
     id: int
     success: bool
     output: Optional[Any] = None
+    output_ref: Optional[str] = None
     timings: Optional[list[Timing]] = None
     error: Optional[str] = None
 
@@ -89,12 +137,17 @@ class SingleModuleValidatedEval:
     validator: Validator
     id: int
     validations: dict[int, Analysis]
+    scratch: Optional[str]
+    refs: bool
 
     def __init__(self, *, module: str, validator: Validator):
         self.module = module
         self.validator = validator
         self.id = 0
         self.validations = {}
+        self.scratch = os.environ.get("GRADBENCH_SCRATCH")
+        self.arrays = False
+        self.refs = False
 
     def send(self, message: Any) -> Any:
         json.dump({"id": self.id} | message, sys.stdout)
@@ -114,27 +167,63 @@ class SingleModuleValidatedEval:
         message = {"kind": "start", "eval": self.module}
         if config is not None:
             message["config"] = config
+        message["features"] = ["arrays"]
+        if self.scratch is not None:
+            message["features"].append("refs")
         response = StartResponse.model_validate(self.send(message))
+        features = response.features or []
+        self.arrays = "arrays" in features
+        self.refs = self.scratch is not None and "refs" in features
         return response
 
-    def define(self) -> DefineResponse:
+    def define(self, *, timeout: Optional[float] = None) -> DefineResponse:
         message = {"kind": "define", "module": self.module}
+        if timeout is not None:
+            message["timeout"] = timeout
         response = DefineResponse.model_validate(self.send(message))
         return response
 
     def evaluate(
-        self, *, function: str, input: Any, description: Optional[str] = None
+        self,
+        *,
+        function: str,
+        input: Any,
+        description: Optional[str] = None,
+        timeout: Optional[float] = None,
     ) -> EvaluateResponse:
         message = {
             "kind": "evaluate",
             "module": self.module,
             "function": function,
-            "input": input,
         }
+        id = self.id
+        payload = encode_arrays(input) if self.arrays else input
+        input_path = None
+        if self.refs:
+            # Pass the input as a file in the scratch directory, to save the
+            # intermediary and tool from parsing and logging it.
+            message["input_ref"] = f"input-{id}.json"
+            input_path = os.path.join(self.scratch, message["input_ref"])
+            with open(input_path, "w") as f:
+                json.dump(payload, f)
+        else:
+            message["input"] = payload
         if description is not None:
             message["description"] = description
-        id = self.id
-        response = EvaluateResponse.model_validate(self.send(message))
+        if timeout is not None:
+            message["timeout"] = timeout
+        try:
+            response = EvaluateResponse.model_validate(self.send(message))
+        finally:
+            if input_path is not None:
+                os.remove(input_path)
+        if response.output_ref is not None:
+            output_path = os.path.join(self.scratch, response.output_ref)
+            with open(output_path) as f:
+                response.output = json.load(f)
+            os.remove(output_path)
+        if self.arrays and response.output is not None:
+            response.output = decode_arrays(response.output)
         if response.success:
             analysis = self.validator(function, input, response.output)
             self.analysis(of=id, valid=analysis.valid, error=analysis.error)